@@ -0,0 +1,21 @@
+use music_analyzer_generator::{smf::read_recording, PitchSequence};
+
+fn main() -> anyhow::Result<()> {
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.len() < 2 {
+        println!("Usage: beat_print filename [-beats]")
+    }
+    let seq = PitchSequence::new(&read_recording(args[1].as_str(), false)?);
+    match seq.beat_grid() {
+        Some(grid) => {
+            println!("tempo: {:.1} bpm", grid.beats_per_minute());
+            if args.contains(&"-beats".to_string()) {
+                for (i, time) in grid.beats().iter().enumerate() {
+                    println!("{i}\t{time:.3}\t{:.1} bpm", grid.beats_per_minute_at(*time));
+                }
+            }
+        }
+        None => println!("Too few notes to find a beat"),
+    }
+    Ok(())
+}
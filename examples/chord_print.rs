@@ -1,21 +1,43 @@
 use midi_note_recorder::Recording;
-use music_analyzer_generator::PitchSequence;
+use music_analyzer_generator::{harmony::RomanNumeral, smf::read_recording, Key, PitchSequence};
 
 fn main() -> anyhow::Result<()> {
     let args = std::env::args().collect::<Vec<_>>();
     if args.len() < 2 {
-        println!("Usage: chord_print filename [-times]")
+        println!(
+            "Usage: chord_print filename [-times] [-names] [-key \"D dorian\"] [-accompaniment]"
+        )
     }
-    let recording: Recording = Recording::from_file(args[1].as_str())?;
-    let chords = PitchSequence::new(&recording).chords_starts_durations();
+    let recording: Recording = read_recording(args[1].as_str(), false)?;
+    let mut seq = PitchSequence::new(&recording);
+    if args.contains(&"-accompaniment".to_string()) {
+        seq = seq.melody_and_accompaniment().1;
+    }
+    let chords = seq.chord_spans();
+    let key = match args.iter().position(|a| a == "-key") {
+        Some(i) => Some(args[i + 1].parse::<Key>()?),
+        None => None,
+    };
     if args.contains(&"-debug".to_string()) {
         println!("{chords:?}");
     } else {
-        for (chord, time, duration) in chords {
+        for span in chords {
+            let chord = span.chord();
             if args.contains(&"-times".to_string()) {
-                print!("time: {time:.2}\tduration: {duration:.2}\t");
+                print!(
+                    "time: {:.2}\tduration: {:.2}\t",
+                    span.start(),
+                    span.duration()
+                );
+            }
+            if let Some(key) = key {
+                print!("{}\t", RomanNumeral::new(key, chord.name()));
+            }
+            if args.contains(&"-names".to_string()) {
+                println!("{chord:#}");
+            } else {
+                println!("{chord}");
             }
-            println!("{chord}");
         }
     }
     Ok(())
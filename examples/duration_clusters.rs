@@ -1,12 +1,26 @@
 use midi_note_recorder::Recording;
-use music_analyzer_generator::{consolidated_note_rest_times, duration_clusters, durations_notes_from};
+use music_analyzer_generator::{consolidated_note_rest_times, duration_clusters, durations_notes_from, quantizer::Grid, smf::read_recording, PitchSequence};
 
 fn main() -> anyhow::Result<()> {
     let args = std::env::args().collect::<Vec<_>>();
     if args.len() < 2 {
-        println!("Usage: duration_print filename [-debug]")
+        println!("Usage: duration_print filename [-debug] [-beats [-quantize]]")
+    }
+    let mut recording: Recording = read_recording(args[1].as_str(), false)?;
+    if args.contains(&"-beats".to_string()) {
+        let seq = PitchSequence::new(&recording);
+        if let Some(grid) = seq.beat_grid() {
+            println!("tempo: {:.1} bpm", grid.beats_per_minute());
+            let seq = if args.contains(&"-quantize".to_string()) {
+                let (quantized, error) = seq.quantized(&grid, &Grid::Mixed(vec![4, 3]));
+                println!("quantization error: mean {:.3}, max {:.3} beats", error.mean(), error.max());
+                quantized
+            } else {
+                seq
+            };
+            recording = seq.in_beats(&grid).recording();
+        }
     }
-    let recording: Recording = Recording::from_file(args[1].as_str())?;
     let durations_notes = durations_notes_from(&recording);
 
     let c = consolidated_note_rest_times(&durations_notes);
@@ -1,14 +1,35 @@
 use midi_note_recorder::Recording;
-use music_analyzer_generator::{durations_notes_from, NoteName};
+use music_analyzer_generator::{
+    durations_notes_from, key_finder::keys_from, notes_from, smf::read_recording,
+    voice_separator::melody_and_accompaniment_from, Pitch,
+};
 
 fn main() -> anyhow::Result<()> {
     let args = std::env::args().collect::<Vec<_>>();
     if args.len() < 2 {
-        println!("Usage: duration_print filename")
+        println!("Usage: duration_print filename [-notes] [-melody]")
     }
-    let recording: Recording = Recording::from_file(args[1].as_str())?;
-    for (d, n, v) in durations_notes_from(&recording) {
-        println!("{d:.2}\t{n}\t{}\t{v}", NoteName::name_of(n));
+    let mut recording: Recording = read_recording(args[1].as_str(), false)?;
+    if args.contains(&"-melody".to_string()) {
+        recording = melody_and_accompaniment_from(&recording).0;
+    }
+    let key = keys_from(&recording).first().map(|(key, _)| *key);
+    let spelled = |n: u8| key.map_or(Pitch::from_midi(n), |key| key.spell_pitch(n));
+    if args.contains(&"-notes".to_string()) {
+        for note in notes_from(&recording) {
+            println!(
+                "{:.2}\t{:.2}\t{}\t{}\t{}",
+                note.start(),
+                note.duration(),
+                note.pitch(),
+                spelled(note.pitch()),
+                note.velocity()
+            );
+        }
+    } else {
+        for (d, n, v) in durations_notes_from(&recording) {
+            println!("{d:.2}\t{n}\t{}\t{v}", spelled(n));
+        }
     }
     Ok(())
 }
@@ -0,0 +1,40 @@
+use midi_note_recorder::Recording;
+use music_analyzer_generator::{
+    key_finder::{key_regions, keys_from},
+    smf::read_recording,
+    PitchSequence, ScaleMode,
+};
+
+fn main() -> anyhow::Result<()> {
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.len() < 2 {
+        println!("Usage: key_print filename [num_keys] [-regions]")
+    }
+    let recording: Recording = read_recording(args[1].as_str(), false)?;
+    if args.contains(&"-regions".to_string()) {
+        let seq = PitchSequence::new(&recording);
+        for region in key_regions(&seq, &[ScaleMode::Major, ScaleMode::Minor], 8.0, 2.0) {
+            println!(
+                "{:.2}-{:.2}\t{}",
+                region.start(),
+                region.end(),
+                region.key()
+            );
+            for pivot in region.pivots() {
+                println!(
+                    "\tpivot at {:.2}: {} ({} -> {})",
+                    pivot.start(),
+                    pivot.chord().name(),
+                    pivot.before(),
+                    pivot.after()
+                );
+            }
+        }
+    } else {
+        let num_keys = args.get(2).map_or(Ok(5), |n| n.parse::<usize>())?;
+        for (key, score) in keys_from(&recording).iter().take(num_keys) {
+            println!("{key}\t{score:.3}");
+        }
+    }
+    Ok(())
+}
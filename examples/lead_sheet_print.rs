@@ -0,0 +1,46 @@
+use midi_note_recorder::note_velocity_from;
+use music_analyzer_generator::{
+    durations_notes_from,
+    lead_sheet::{abc_from, lilypond_from},
+    smf::read_recording,
+    Key, PitchSequence,
+};
+
+fn main() -> anyhow::Result<()> {
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.len() < 2 {
+        println!(
+            "Usage: lead_sheet_print filename [-abc] [-key \"D dorian\"] [-bpm 120] [-beats 4]"
+        )
+    }
+    let recording = read_recording(args[1].as_str(), false)?;
+    let seq = PitchSequence::new(&recording);
+    let key = match args.iter().position(|a| a == "-key") {
+        Some(i) => args[i + 1].parse::<Key>()?,
+        None => seq.keys()[0].0,
+    };
+    let bpm = match args.iter().position(|a| a == "-bpm") {
+        Some(i) => args[i + 1].parse::<f64>()?,
+        None => 120.0,
+    };
+    let beats = match args.iter().position(|a| a == "-beats") {
+        Some(i) => args[i + 1].parse::<u8>()?,
+        None => 4,
+    };
+    let start = recording
+        .midi_queue()
+        .iter()
+        .find(|(_, msg)| note_velocity_from(msg).is_some())
+        .map_or(0.0, |(t, _)| *t);
+    let melody = durations_notes_from(&recording);
+    let chords = seq.chords_starts_durations();
+    if args.contains(&"-abc".to_string()) {
+        print!("{}", abc_from(&melody, start, &chords, key, bpm, beats));
+    } else {
+        print!(
+            "{}",
+            lilypond_from(&melody, start, &chords, key, bpm, beats)
+        );
+    }
+    Ok(())
+}
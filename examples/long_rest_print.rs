@@ -1,6 +1,7 @@
 use midi_note_recorder::Recording;
 use music_analyzer_generator::{
-    consolidated_note_rest_times, durations_notes_from, partitioned_melody, NoteName,
+    consolidated_note_rest_times, durations_notes_from, partitioned_melody, smf::read_recording,
+    Pitch,
 };
 
 fn main() -> anyhow::Result<()> {
@@ -8,7 +9,7 @@ fn main() -> anyhow::Result<()> {
     if args.len() < 2 {
         println!("Usage: duration_print filename")
     }
-    let recording: Recording = Recording::from_file(args[1].as_str())?;
+    let recording: Recording = read_recording(args[1].as_str(), false)?;
     let durations_notes = durations_notes_from(&recording);
 
     let c = consolidated_note_rest_times(&durations_notes);
@@ -21,7 +22,7 @@ fn main() -> anyhow::Result<()> {
                 "{:.2}\t{}\t{}\t{}",
                 c[i].0,
                 c[i].1,
-                NoteName::name_of(c[i].1),
+                Pitch::from_midi(c[i].1),
                 c[i].2
             );
         }
@@ -0,0 +1,32 @@
+use music_analyzer_generator::{smf::read_recording, PitchSequence};
+
+fn main() -> anyhow::Result<()> {
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.len() < 2 {
+        println!("Usage: meter_print filename [-chords]")
+    }
+    let seq = PitchSequence::new(&read_recording(args[1].as_str(), false)?);
+    match seq.meter() {
+        Some(meter) => {
+            println!(
+                "{} at {:.1} bpm, {} beats per bar",
+                meter.time_signature(),
+                meter.beat_grid().beats_per_minute(),
+                meter.beats_per_bar()
+            );
+            if args.contains(&"-chords".to_string()) {
+                for (chord, bar, beat, beats) in
+                    meter.chords_in_bars(&seq.chords_starts_durations())
+                {
+                    println!("{bar}:{beat:.2}\t{beats:.2}\t{chord}");
+                }
+            } else {
+                for (i, time) in meter.bar_starts().iter().enumerate() {
+                    println!("{i}\t{time:.3}");
+                }
+            }
+        }
+        None => println!("Too few notes to find a meter"),
+    }
+    Ok(())
+}
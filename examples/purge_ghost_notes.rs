@@ -1,5 +1,4 @@
-use midi_note_recorder::Recording;
-use music_analyzer_generator::PitchSequence;
+use music_analyzer_generator::{smf::read_recording, PitchSequence};
 
 fn main() -> anyhow::Result<()> {
     let args = std::env::args().collect::<Vec<_>>();
@@ -13,7 +12,7 @@ fn main() -> anyhow::Result<()> {
     let min_velocity = args[3].parse::<u8>()?;
     let output_filename = args[4].as_str();
 
-    let recording = Recording::from_file(input_filename)?;
+    let recording = read_recording(input_filename, false)?;
     let recording = PitchSequence::new(&recording)
         .without_notes_below(min_duration, min_velocity)
         .recording();
@@ -1,7 +1,7 @@
 use std::sync::{Arc, Mutex};
 
 use midi_note_recorder::Recording;
-use music_analyzer_generator::{consolidated_note_rest_times, duration_clusters, durations_notes_from, generator::random_chord_note_melody, PitchSequence};
+use music_analyzer_generator::{consolidated_note_rest_times, duration_clusters, durations_notes_from, generator::random_chord_note_melody, smf::read_recording, PitchSequence};
 
 use crossbeam_queue::SegQueue;
 use midi_fundsp::{
@@ -14,7 +14,7 @@ fn main() -> anyhow::Result<()> {
     if args.len() < 2 {
         println!("Usage: duration_print filename [-debug]")
     }
-    let recording = Recording::from_file(args[1].as_str())?;
+    let recording = read_recording(args[1].as_str(), false)?;
     let chords = PitchSequence::new(&recording).chords_starts_durations();
     
     let durations_notes = durations_notes_from(&recording);
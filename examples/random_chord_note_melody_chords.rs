@@ -1,7 +1,7 @@
 use std::sync::{Arc, Mutex};
 
 use midi_note_recorder::{Recording, stereo_playback};
-use music_analyzer_generator::{consolidated_note_rest_times, duration_clusters, durations_notes_from, generator::random_chord_note_melody, PitchSequence};
+use music_analyzer_generator::{consolidated_note_rest_times, duration_clusters, durations_notes_from, generator::random_chord_note_melody, musicxml::musicxml_from, smf::{read_recording, write_smf, DEFAULT_TICKS_PER_QUARTER}, Key, PitchSequence};
 
 use crossbeam_queue::SegQueue;
 use midi_fundsp::{
@@ -12,9 +12,9 @@ use midi_fundsp::{
 fn main() -> anyhow::Result<()> {
     let args = std::env::args().collect::<Vec<_>>();
     if args.len() < 2 {
-        println!("Usage: duration_print filename [-debug]")
+        println!("Usage: random_chord_note_melody_chords filename [-output out.mid [-ppq 480]] [-musicxml out.musicxml [-key \"D dorian\"]] [-bpm 120]")
     }
-    let recording = Recording::from_file(args[1].as_str())?;
+    let recording = read_recording(args[1].as_str(), false)?;
     let chords = PitchSequence::new(&recording).chords_starts_durations();
     
     let durations_notes = durations_notes_from(&recording);
@@ -22,6 +22,24 @@ fn main() -> anyhow::Result<()> {
     let dc = duration_clusters(&c, 3);
     
     let melody = random_chord_note_melody(&chords, &dc);
+
+    let bpm = option_after(&args, "-bpm").map_or(Ok(120.0), |s| s.parse())?;
+    if let Some(i) = args.iter().position(|a| a == "-musicxml") {
+        let melody_sequence = PitchSequence::new(&Recording::from_sequence(&melody));
+        let key = match option_after(&args, "-key") {
+            Some(key) => key.parse::<Key>()?,
+            None => PitchSequence::new(&recording).keys()[0].0,
+        };
+        std::fs::write(args[i + 1].as_str(), musicxml_from(&melody_sequence, &chords, key, bpm, 4))?;
+        return Ok(());
+    }
+    if let Some(i) = args.iter().position(|a| a == "-output") {
+        let ppq = option_after(&args, "-ppq").map_or(Ok(DEFAULT_TICKS_PER_QUARTER), |s| s.parse())?;
+        let source = recording.midi_queue().into_iter().collect::<Vec<_>>();
+        write_smf(args[i + 1].as_str(), &[("Source", &source[..]), ("Melody", &melody[..])], bpm, ppq)?;
+        return Ok(());
+    }
+
     let melody_recording = Recording::from_sequence(&melody);
 
     let outgoing = Arc::new(SegQueue::new());
@@ -33,4 +51,8 @@ fn main() -> anyhow::Result<()> {
     stereo_playback(&recording, &melody_recording, outgoing, |msg| SynthMsg { msg, speaker: Speaker::Left }, |msg| SynthMsg { msg, speaker: Speaker::Right });
 
     Ok(())
-}
\ No newline at end of file
+}
+
+fn option_after<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1)).map(|s| s.as_str())
+}
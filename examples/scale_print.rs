@@ -0,0 +1,23 @@
+use midi_note_recorder::Recording;
+use music_analyzer_generator::{
+    durations_notes_from, scale_finder::ranked_scales_from_melody, smf::read_recording,
+};
+
+fn main() -> anyhow::Result<()> {
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.len() < 2 {
+        println!("Usage: scale_print filename [num_scales]")
+    }
+    let recording: Recording = read_recording(args[1].as_str(), false)?;
+    let num_scales = args.get(2).map_or(Ok(5), |n| n.parse())?;
+    let melody = durations_notes_from(&recording);
+    for scale in ranked_scales_from_melody(&melody).iter().take(num_scales) {
+        println!(
+            "{}\t{:.3}\toutside: {:?}",
+            scale.key(),
+            scale.fit(),
+            scale.outside()
+        );
+    }
+    Ok(())
+}
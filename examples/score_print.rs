@@ -0,0 +1,25 @@
+use music_analyzer_generator::{
+    musicxml::musicxml_from_sequence, smf::read_recording, Key, PitchSequence,
+};
+
+fn main() -> anyhow::Result<()> {
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.len() < 2 {
+        println!("Usage: score_print filename [-key \"D dorian\"] [-bpm 120] [-beats 4]")
+    }
+    let seq = PitchSequence::new(&read_recording(args[1].as_str(), false)?);
+    let key = match args.iter().position(|a| a == "-key") {
+        Some(i) => Some(args[i + 1].parse::<Key>()?),
+        None => None,
+    };
+    let bpm = match args.iter().position(|a| a == "-bpm") {
+        Some(i) => args[i + 1].parse::<f64>()?,
+        None => 120.0,
+    };
+    let beats = match args.iter().position(|a| a == "-beats") {
+        Some(i) => args[i + 1].parse::<u8>()?,
+        None => 4,
+    };
+    print!("{}", musicxml_from_sequence(&seq, key, bpm, beats));
+    Ok(())
+}
@@ -0,0 +1,326 @@
+use midi_note_recorder::{note_velocity_from, Recording};
+
+use crate::PitchSequence;
+
+/// Resolution, in seconds, of the onset strength signal.
+const FRAME: f64 = 0.01;
+
+/// Tempo range considered when estimating the beat period.
+const MIN_BEATS_PER_MINUTE: f64 = 40.0;
+const MAX_BEATS_PER_MINUTE: f64 = 200.0;
+
+/// Center of the tempo preference applied to the autocorrelation, and its
+/// spread in octaves. Without it a tempo and its double score alike.
+const PREFERRED_BEATS_PER_MINUTE: f64 = 120.0;
+const TEMPO_SPREAD_OCTAVES: f64 = 1.0;
+
+/// How strongly the tracker penalizes a beat interval that departs from
+/// the estimated period. Lower values follow tempo changes more freely.
+const TIGHTNESS: f64 = 100.0;
+
+/// Spread, in frames, of each onset over the strength signal, so that
+/// slightly early or late notes still reinforce the same beat.
+const ONSET_SPREAD_FRAMES: i64 = 2;
+
+/// Beat times in seconds, at least two and in increasing order. Times
+/// between beats are interpolated linearly; times outside the grid are
+/// extrapolated from the nearest beat interval.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BeatGrid {
+    beats: Vec<f64>,
+}
+
+impl BeatGrid {
+    /// `None` unless `beats` holds at least two times in increasing order.
+    pub fn new(beats: Vec<f64>) -> Option<Self> {
+        if beats.len() >= 2 && beats.windows(2).all(|w| w[0] < w[1]) {
+            Some(Self { beats })
+        } else {
+            None
+        }
+    }
+
+    /// A grid at a steady tempo, with `num_beats` beats from `first_beat`.
+    /// `None` unless the tempo is positive and finite.
+    pub fn steady(beats_per_minute: f64, first_beat: f64, num_beats: usize) -> Option<Self> {
+        if !(beats_per_minute.is_finite() && beats_per_minute > 0.0) {
+            return None;
+        }
+        let period = 60.0 / beats_per_minute;
+        Self::new(
+            (0..num_beats)
+                .map(|i| first_beat + i as f64 * period)
+                .collect(),
+        )
+    }
+
+    pub fn beats(&self) -> &[f64] {
+        &self.beats
+    }
+
+    /// The tempo from the median beat interval, which ignores the odd
+    /// stretched or hurried beat.
+    pub fn beats_per_minute(&self) -> f64 {
+        let mut intervals = self
+            .beats
+            .windows(2)
+            .map(|w| w[1] - w[0])
+            .collect::<Vec<_>>();
+        intervals.sort_by(|a, b| a.total_cmp(b));
+        60.0 / intervals[intervals.len() / 2]
+    }
+
+    /// The tempo of the beat interval containing `time`.
+    pub fn beats_per_minute_at(&self, time: f64) -> f64 {
+        let i = self.interval_index(time);
+        60.0 / (self.beats[i + 1] - self.beats[i])
+    }
+
+    /// Where `time` falls, counting the first beat as 0.
+    pub fn beat_at(&self, time: f64) -> f64 {
+        let i = self.interval_index(time);
+        i as f64 + (time - self.beats[i]) / (self.beats[i + 1] - self.beats[i])
+    }
+
+    /// The inverse of `beat_at`.
+    pub fn time_at(&self, beat: f64) -> f64 {
+        let i = (beat.floor().max(0.0) as usize).min(self.beats.len() - 2);
+        self.beats[i] + (beat - i as f64) * (self.beats[i + 1] - self.beats[i])
+    }
+
+    /// `melody`, in the form returned by `durations_notes_from`, with each
+    /// duration measured in beats. The melody begins at time `start`.
+    pub fn melody_in_beats(&self, melody: &[(f64, u8, u8)], start: f64) -> Vec<(f64, u8, u8)> {
+        let mut time = start;
+        melody
+            .iter()
+            .map(|(duration, note, velocity)| {
+                let beats = self.beat_at(time + duration) - self.beat_at(time);
+                time += duration;
+                (beats, *note, *velocity)
+            })
+            .collect()
+    }
+
+    /// The beat interval used for `time`: the one containing it, or the
+    /// first or last for times outside the grid.
+    fn interval_index(&self, time: f64) -> usize {
+        self.beats
+            .partition_point(|b| *b <= time)
+            .saturating_sub(1)
+            .min(self.beats.len() - 2)
+    }
+}
+
+pub fn beat_grid_from(recording: &Recording) -> Option<BeatGrid> {
+    PitchSequence::new(recording).beat_grid()
+}
+
+/// Tracks the beat through the note onsets of `seq`. The period is estimated
+/// by autocorrelating onset strength, preferring tempos near 120 beats per
+/// minute; beats are then placed by dynamic programming to fall on strong
+/// onsets while keeping each interval near the period, so the grid follows
+/// gradual changes in tempo. `None` if `seq` has fewer than two onsets.
+pub fn beat_grid(seq: &PitchSequence) -> Option<BeatGrid> {
+    let onsets = seq
+        .seq
+        .iter()
+        .filter_map(|(time, msg, _)| note_velocity_from(msg).map(|(_, v)| (*time, v)))
+        .filter(|(_, velocity)| *velocity > 0)
+        .map(|(time, velocity)| (time, velocity as f64 / 127.0))
+        .collect::<Vec<_>>();
+    if onsets.len() < 2 {
+        return None;
+    }
+    let strength = onset_strength(&onsets);
+    let period = beat_period(&strength);
+    let beats = tracked_beats(&strength, period)
+        .iter()
+        .map(|frame| *frame as f64 * FRAME)
+        .collect::<Vec<_>>();
+    let period = period as f64 * FRAME;
+    match beats[..] {
+        [only] => BeatGrid::new(vec![only, only + period]),
+        _ => BeatGrid::new(beats),
+    }
+}
+
+/// Onset weights summed into frames, each spread over its neighbors, then
+/// scaled to unit standard deviation.
+fn onset_strength(onsets: &[(f64, f64)]) -> Vec<f64> {
+    let frame_of = |time: f64| (time.max(0.0) / FRAME).round() as i64;
+    let last = onsets.iter().map(|(t, _)| frame_of(*t)).max().unwrap();
+    let mut result = vec![0.0; last as usize + 1];
+    for (time, weight) in onsets.iter() {
+        let frame = frame_of(*time);
+        for offset in -2 * ONSET_SPREAD_FRAMES..=2 * ONSET_SPREAD_FRAMES {
+            let spread = offset as f64 / ONSET_SPREAD_FRAMES as f64;
+            if let Some(s) = usize::try_from(frame + offset)
+                .ok()
+                .and_then(|f| result.get_mut(f))
+            {
+                *s += weight * (-0.5 * spread * spread).exp();
+            }
+        }
+    }
+    let mean = result.iter().sum::<f64>() / result.len() as f64;
+    let deviation =
+        (result.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / result.len() as f64).sqrt();
+    if deviation > 0.0 {
+        for s in result.iter_mut() {
+            *s /= deviation;
+        }
+    }
+    result
+}
+
+/// The beat period in frames: the lag whose autocorrelation, weighted by
+/// the tempo preference, is greatest.
+fn beat_period(strength: &[f64]) -> usize {
+    let min_lag = (60.0 / MAX_BEATS_PER_MINUTE / FRAME) as usize;
+    let max_lag = (60.0 / MIN_BEATS_PER_MINUTE / FRAME) as usize;
+    let preferred = 60.0 / PREFERRED_BEATS_PER_MINUTE;
+    (min_lag..=max_lag)
+        .map(|lag| {
+            let correlation = strength
+                .iter()
+                .zip(strength.iter().skip(lag))
+                .map(|(a, b)| a * b)
+                .sum::<f64>();
+            let octaves = (lag as f64 * FRAME / preferred).log2() / TEMPO_SPREAD_OCTAVES;
+            (lag, correlation * (-0.5 * octaves * octaves).exp())
+        })
+        .fold((min_lag, f64::MIN), |best, (lag, score)| {
+            if score > best.1 {
+                (lag, score)
+            } else {
+                best
+            }
+        })
+        .0
+}
+
+/// The frames of the best-scoring beat sequence. Each frame scores its
+/// onset strength plus the best score of a previous beat between half and
+/// twice the period earlier, less a penalty for how far that interval is
+/// from the period. The sequence ends on the best frame within the last
+/// period.
+fn tracked_beats(strength: &[f64], period: usize) -> Vec<usize> {
+    let mut scores = vec![0.0; strength.len()];
+    let mut previous: Vec<Option<usize>> = vec![None; strength.len()];
+    for frame in 0..strength.len() {
+        let earliest = frame.saturating_sub(2 * period);
+        let best = (earliest..(frame + 1).saturating_sub(period.div_ceil(2)))
+            .map(|before| {
+                let stretch = ((frame - before) as f64 / period as f64).ln();
+                (before, scores[before] - TIGHTNESS * stretch * stretch)
+            })
+            .fold(
+                None,
+                |best: Option<(usize, f64)>, (before, score)| match best {
+                    Some((_, best_score)) if best_score >= score => best,
+                    _ => Some((before, score)),
+                },
+            );
+        scores[frame] = strength[frame];
+        if let Some((before, score)) = best.filter(|(_, score)| *score > 0.0) {
+            scores[frame] += score;
+            previous[frame] = Some(before);
+        }
+    }
+    let from = strength.len().saturating_sub(period);
+    let last = (from..strength.len()).fold(from, |best, frame| {
+        if scores[frame] > scores[best] {
+            frame
+        } else {
+            best
+        }
+    });
+    let mut result = vec![last];
+    while let Some(before) = previous[*result.last().unwrap()] {
+        result.push(before);
+    }
+    result.reverse();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use midi_msg::Channel;
+
+    use crate::beat_tracker::{beat_grid, BeatGrid};
+    use crate::{Note, PitchSequence};
+
+    /// A tenth of a second of middle C.
+    fn onset(time: f64, velocity: u8) -> Note {
+        Note::new(time, 0.1, 60, velocity, Channel::Ch1)
+    }
+
+    #[test]
+    fn test_steady_tempo() {
+        // 100 beats per minute, with an eighth note after every other beat.
+        let mut onsets = vec![];
+        for i in 0..32 {
+            let time = 0.5 + 0.6 * i as f64;
+            onsets.push(onset(time, 100));
+            if i % 2 == 1 {
+                onsets.push(onset(time + 0.3, 60));
+            }
+        }
+        let grid = beat_grid(&PitchSequence::from_notes(&onsets)).unwrap();
+        assert!((grid.beats_per_minute() - 100.0).abs() < 1.0);
+        assert_eq!(grid.beats().len(), 32);
+        assert!((grid.beats()[0] - 0.5).abs() < 0.02);
+        assert!((grid.beat_at(0.5 + 0.6 * 10.5) - 10.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_tempo_drift() {
+        // Accelerates steadily from 100 to 120 beats per minute.
+        let mut onsets = vec![];
+        let mut beats = vec![];
+        let mut time = 0.2;
+        for i in 0..48 {
+            let interval = 0.6 - 0.1 * i as f64 / 47.0;
+            onsets.push(onset(time, 100));
+            beats.push(time);
+            if i % 3 == 0 {
+                onsets.push(onset(time + interval / 2.0, 50));
+            }
+            time += interval;
+        }
+        let grid = beat_grid(&PitchSequence::from_notes(&onsets)).unwrap();
+        assert_eq!(grid.beats().len(), beats.len());
+        for (tracked, actual) in grid.beats().iter().zip(beats.iter()) {
+            assert!((tracked - actual).abs() < 0.02);
+        }
+        assert!((grid.beats_per_minute_at(1.0) - 100.0).abs() < 3.0);
+        assert!((grid.beats_per_minute_at(25.0) - 120.0).abs() < 4.0);
+    }
+
+    #[test]
+    fn test_beat_grid() {
+        let grid = BeatGrid::new(vec![1.0, 1.5, 2.5]).unwrap();
+        assert_eq!(grid.beat_at(1.25), 0.5);
+        assert_eq!(grid.beat_at(2.0), 1.5);
+        assert_eq!(grid.beat_at(0.5), -1.0);
+        assert_eq!(grid.beat_at(3.5), 3.0);
+        assert_eq!(grid.time_at(1.5), 2.0);
+        assert_eq!(grid.time_at(-1.0), 0.5);
+        assert_eq!(grid.beats_per_minute_at(2.0), 60.0);
+        assert_eq!(
+            grid.melody_in_beats(&[(0.5, 60, 100), (0.0, 60, 0), (1.0, 62, 100)], 1.0),
+            vec![(1.0, 60, 100), (0.0, 60, 0), (1.0, 62, 100)]
+        );
+        assert!(BeatGrid::new(vec![1.0]).is_none());
+        assert!(BeatGrid::new(vec![1.0, 1.0]).is_none());
+
+        assert_eq!(
+            BeatGrid::steady(120.0, 1.0, 2).unwrap().beats(),
+            &[1.0, 1.5]
+        );
+        for tempo in [0.0, -60.0, f64::NAN, f64::INFINITY] {
+            assert!(BeatGrid::steady(tempo, 1.0, 2).is_none());
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::Chord;
+use crate::{Chord, Key};
 use midi_msg::MidiMsg;
 use midi_note_recorder::{midi_msg_from, note_velocity_from};
 use rand::prelude::*;
@@ -60,9 +60,88 @@ pub fn random_chord_note_melody(chords: &Vec<(Chord, f64, f64)>, duration_candid
     }, chords, duration_candidates)
 }
 
+pub fn random_scale_note_melody(key: Key, chords: &Vec<(Chord, f64, f64)>, duration_candidates: &Vec<Vec<f64>>) -> Vec<(f64, MidiMsg)> {
+    random_melody_from(|chord, _| {
+        let mut rng = thread_rng();
+        let lowest = chord.notes.iter().min().unwrap() + 12;
+        let highest = chord.notes.iter().max().unwrap() + 12;
+        let note_candidates = key.mode().notes_going_up(key.tonic()).filter(|n| lowest <= *n && *n <= highest).collect::<Vec<_>>();
+        let note = *note_candidates.choose(&mut rng).unwrap_or(&lowest);
+        midi_msg_from(midi_msg::Channel::Ch1, note, 127)
+    }, chords, duration_candidates)
+}
+
+pub fn random_chord_scale_melody(chords: &Vec<(Chord, f64, f64)>, duration_candidates: &Vec<Vec<f64>>) -> Vec<(f64, MidiMsg)> {
+    random_melody_from(|chord, _| {
+        let mut rng = thread_rng();
+        let lowest = chord.notes.iter().min().unwrap() + 12;
+        let highest = chord.notes.iter().max().unwrap() + 12;
+        let scale = *chord.name.mode().scales().choose(&mut rng).unwrap();
+        let note_candidates = scale.notes_going_up(chord.name.root()).filter(|n| lowest <= *n && *n <= highest).collect::<Vec<_>>();
+        let note = *note_candidates.choose(&mut rng).unwrap_or(&lowest);
+        midi_msg_from(midi_msg::Channel::Ch1, note, 127)
+    }, chords, duration_candidates)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::generator::random_durations_from;
+    use midi_msg::MidiMsg;
+    use midi_note_recorder::{note_velocity_from, Recording};
+
+    use crate::generator::{
+        random_chord_scale_melody, random_durations_from, random_scale_note_melody,
+    };
+    use crate::PitchSequence;
+
+    fn pitches_played(melody: &[(f64, MidiMsg)]) -> Vec<u8> {
+        melody
+            .iter()
+            .filter_map(|(_, msg)| note_velocity_from(msg))
+            .filter(|(_, velocity)| *velocity > 0)
+            .map(|(note, _)| note)
+            .collect()
+    }
+
+    #[test]
+    fn test_scale_note_melody() {
+        let seq = PitchSequence::new(&Recording::from_file("up_down").unwrap());
+        let chords = seq.chords_starts_durations();
+        let key = seq.keys()[0].0;
+        let scale = key.pitch_classes();
+        for _ in 0..20 {
+            let melody = random_scale_note_melody(key, &chords, &vec![vec![0.25], vec![0.5]]);
+            let pitches = pitches_played(&melody);
+            assert!(!pitches.is_empty());
+            assert!(
+                pitches.iter().all(|p| scale.contains(&(p % 12))),
+                "{pitches:?} outside {key:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_chord_scale_melody() {
+        let seq = PitchSequence::new(&Recording::from_file("up_down").unwrap());
+        for (chord, _, _) in seq.chords_starts_durations() {
+            let name = chord.name();
+            let scales = name
+                .mode()
+                .scales()
+                .iter()
+                .map(|s| s.pitch_classes(name.root()))
+                .collect::<Vec<_>>();
+            let melody =
+                random_chord_scale_melody(&vec![(chord, 0.0, 2.0)], &vec![vec![0.25], vec![0.5]]);
+            let pitches = pitches_played(&melody);
+            assert!(!pitches.is_empty());
+            for p in pitches {
+                assert!(
+                    scales.iter().any(|scale| scale.contains(&(p % 12))),
+                    "{p} outside the scales of {name}"
+                );
+            }
+        }
+    }
 
     #[test]
     fn test_make_durations() {
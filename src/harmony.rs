@@ -0,0 +1,346 @@
+use std::fmt::Display;
+
+use enum_iterator::all;
+
+use crate::{Accidental, Chord, ChordMode, ChordName, Key, NoteName, ScaleMode};
+
+const NUMERALS: [&str; 7] = ["I", "II", "III", "IV", "V", "VI", "VII"];
+
+/// How a chord relates to the key it is analyzed in.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum HarmonicFunction {
+    Diatonic,
+    /// A dominant or leading-tone chord applied to another scale degree
+    /// (0-based), such as V/V. `applied` is its degree relative to the
+    /// target, 4 for V or 6 for vii, and `target_minor` gives the case of
+    /// the target.
+    Secondary {
+        applied: usize,
+        target: usize,
+        target_minor: bool,
+    },
+    /// Not in the key, but diatonic to another mode on the same tonic.
+    Borrowed,
+    Chromatic,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct RomanNumeral {
+    degree: usize,
+    alteration: Accidental,
+    mode: ChordMode,
+    function: HarmonicFunction,
+}
+
+impl RomanNumeral {
+    /// Diatonic and secondary chords are spelled as in `key`; other chords
+    /// keep the spelling of `name`, so in C major C♯ minor is ♯i and D♭
+    /// major is ♭II.
+    pub fn new(key: Key, name: ChordName) -> Self {
+        let root = name.root().pitch_class();
+        let members = name.mode().pitch_classes(root);
+        let diatonic = diatonic_scales(key);
+        if let Some(scale) = diatonic.iter().find(|scale| contains_all(scale, &members)) {
+            let function = HarmonicFunction::Diatonic;
+            return Self::on_scale(key, scale, key.spell_chord(name), function);
+        }
+
+        let scale = &diatonic[0];
+        if let Some((applied, target, target_minor)) = secondary_target(scale, root, name.mode()) {
+            let function = HarmonicFunction::Secondary {
+                applied,
+                target,
+                target_minor,
+            };
+            return Self::on_scale(key, scale, key.spell_chord(name), function);
+        }
+
+        let function = if all::<ScaleMode>()
+            .filter(|mode| mode.is_tonal() && !diatonic_modes(key).contains(mode))
+            .any(|mode| contains_all(&mode.pitch_classes(key.tonic()), &members))
+        {
+            HarmonicFunction::Borrowed
+        } else {
+            HarmonicFunction::Chromatic
+        };
+        Self::on_scale(key, scale, name, function)
+    }
+
+    fn on_scale(key: Key, scale: &[u8], name: ChordName, function: HarmonicFunction) -> Self {
+        let (degree, alteration) = degree_alteration(key.tonic(), scale, name.root());
+        Self {
+            degree,
+            alteration,
+            mode: name.mode(),
+            function,
+        }
+    }
+
+    /// The scale degree of the root, counting the tonic as 0. For secondary
+    /// chords this is the degree in the key, so V/V in C major is on 1; the
+    /// degree relative to the target is in the `HarmonicFunction`.
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    pub fn alteration(&self) -> Accidental {
+        self.alteration
+    }
+
+    pub fn mode(&self) -> ChordMode {
+        self.mode
+    }
+
+    pub fn function(&self) -> HarmonicFunction {
+        self.function
+    }
+}
+
+impl Display for RomanNumeral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let minor = self.mode.has_minor_third();
+        if let HarmonicFunction::Secondary {
+            applied,
+            target,
+            target_minor,
+        } = self.function
+        {
+            return write!(
+                f,
+                "{}{}/{}",
+                numeral(applied, minor),
+                self.mode.figure(),
+                numeral(target, target_minor)
+            );
+        }
+        if self.alteration != Accidental::Natural {
+            write!(f, "{}", self.alteration.symbol())?;
+        }
+        write!(f, "{}{}", numeral(self.degree, minor), self.mode.figure())
+    }
+}
+
+/// Labels each chord of a timeline such as the one from
+/// `PitchSequence::chords_starts_durations` with its Roman numeral in `key`.
+pub fn roman_numerals_from(
+    key: Key,
+    chords: &[(Chord, f64, f64)],
+) -> Vec<(RomanNumeral, f64, f64)> {
+    chords
+        .iter()
+        .map(|(chord, start, duration)| (RomanNumeral::new(key, chord.name()), *start, *duration))
+        .collect()
+}
+
+fn numeral(degree: usize, minor: bool) -> String {
+    if minor {
+        NUMERALS[degree].to_lowercase()
+    } else {
+        NUMERALS[degree].to_string()
+    }
+}
+
+/// Modes whose chords count as diatonic in `key`. Minor keys also admit the
+/// raised sixth and seventh degrees. Keys on scales without seven notes are
+/// analyzed against the major or minor scale sharing their third.
+fn diatonic_modes(key: Key) -> Vec<ScaleMode> {
+    match key.heptatonic().mode() {
+        ScaleMode::Minor => vec![
+            ScaleMode::Minor,
+            ScaleMode::HarmonicMinor,
+            ScaleMode::MelodicMinor,
+        ],
+        mode => vec![mode],
+    }
+}
+
+fn diatonic_scales(key: Key) -> Vec<Vec<u8>> {
+    diatonic_modes(key)
+        .iter()
+        .map(|mode| mode.pitch_classes(key.tonic()))
+        .collect()
+}
+
+fn contains_all(scale: &[u8], members: &[u8]) -> bool {
+    members.iter().all(|m| scale.contains(m))
+}
+
+/// The degree of `root` in the seven-note `scale` on `tonic`, taken from the
+/// letter names as `Key::degree_of` does, and the alteration from the
+/// semitones between `root` and that degree. A spelling more than a double
+/// sharp or flat away falls back to the nearest degree.
+fn degree_alteration(tonic: NoteName, scale: &[u8], root: NoteName) -> (usize, Accidental) {
+    let shift_from = |degree: usize| {
+        let shift = ((root.pitch_class() + 12 - scale[degree]) % 12) as i8;
+        if shift > 6 {
+            shift - 12
+        } else {
+            shift
+        }
+    };
+    let degree = (root.letter().index() + 7 - tonic.letter().index()) % 7;
+    if let Some(alteration) = Accidental::from_semitones(shift_from(degree)) {
+        return (degree, alteration);
+    }
+    let degree = (0..scale.len())
+        .min_by_key(|d| (shift_from(*d).abs(), shift_from(*d) < 0))
+        .unwrap();
+    (
+        degree,
+        Accidental::from_semitones(shift_from(degree)).unwrap(),
+    )
+}
+
+/// If a chord on `root` can act as the dominant (V) or leading-tone chord
+/// (vii) of a major or minor triad on another degree of `scale`, returns its
+/// own degree relative to that target, the target degree, and whether the
+/// target is minor.
+fn secondary_target(scale: &[u8], root: u8, mode: ChordMode) -> Option<(usize, usize, bool)> {
+    let (degree, target_pitch) = match mode {
+        ChordMode::Major
+        | ChordMode::Dominant7
+        | ChordMode::Dominant9
+        | ChordMode::Dominant11
+        | ChordMode::Dominant13 => (4, (root + 5) % 12),
+        ChordMode::Diminished | ChordMode::Diminished7 | ChordMode::HalfDiminished7 => {
+            (6, (root + 1) % 12)
+        }
+        _ => return None,
+    };
+    let target = scale.iter().position(|p| *p == target_pitch)?;
+    if target == 0 {
+        return None;
+    }
+    let third = (scale[(target + 2) % 7] + 12 - target_pitch) % 12;
+    let fifth = (scale[(target + 4) % 7] + 12 - target_pitch) % 12;
+    match (third, fifth) {
+        (4, 7) => Some((degree, target, false)),
+        (3, 7) => Some((degree, target, true)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::harmony::{HarmonicFunction, RomanNumeral};
+    use crate::Accidental::*;
+    use crate::ChordMode::*;
+    use crate::NoteLetter::*;
+    use crate::{ChordName, Key, NoteName, ScaleMode};
+
+    fn chord(
+        note: crate::NoteLetter,
+        accidental: crate::Accidental,
+        mode: crate::ChordMode,
+    ) -> ChordName {
+        ChordName {
+            note,
+            accidental,
+            mode,
+        }
+    }
+
+    #[test]
+    fn test_roman_numerals() {
+        let c_major = Key::new(NoteName::new(C, Natural), ScaleMode::Major);
+        let a_minor = Key::new(NoteName::new(A, Natural), ScaleMode::Minor);
+        for (key, name, expected, function) in [
+            (
+                c_major,
+                chord(C, Natural, Major),
+                "I",
+                HarmonicFunction::Diatonic,
+            ),
+            (
+                c_major,
+                chord(D, Natural, Minor),
+                "ii",
+                HarmonicFunction::Diatonic,
+            ),
+            (
+                c_major,
+                chord(G, Natural, Dominant7),
+                "V7",
+                HarmonicFunction::Diatonic,
+            ),
+            (
+                c_major,
+                chord(B, Natural, HalfDiminished7),
+                "viiø7",
+                HarmonicFunction::Diatonic,
+            ),
+            (
+                c_major,
+                chord(D, Natural, Dominant7),
+                "V7/V",
+                HarmonicFunction::Secondary {
+                    applied: 4,
+                    target: 4,
+                    target_minor: false,
+                },
+            ),
+            (
+                c_major,
+                chord(E, Natural, Major),
+                "V/vi",
+                HarmonicFunction::Secondary {
+                    applied: 4,
+                    target: 5,
+                    target_minor: true,
+                },
+            ),
+            (
+                c_major,
+                chord(A, Flat, Major),
+                "♭VI",
+                HarmonicFunction::Borrowed,
+            ),
+            (
+                c_major,
+                chord(F, Natural, Minor),
+                "iv",
+                HarmonicFunction::Borrowed,
+            ),
+            (
+                c_major,
+                chord(C, Sharp, Minor),
+                "♯i",
+                HarmonicFunction::Chromatic,
+            ),
+            (
+                a_minor,
+                chord(E, Natural, Major),
+                "V",
+                HarmonicFunction::Diatonic,
+            ),
+            (
+                a_minor,
+                chord(G, Sharp, Diminished7),
+                "vii°7",
+                HarmonicFunction::Diatonic,
+            ),
+            (
+                a_minor,
+                chord(C, Natural, Major),
+                "III",
+                HarmonicFunction::Diatonic,
+            ),
+        ] {
+            let numeral = RomanNumeral::new(key, name);
+            assert_eq!(format!("{numeral}"), expected);
+            assert_eq!(numeral.function(), function);
+        }
+    }
+
+    #[test]
+    fn test_secondary_degrees() {
+        let c_major = Key::new(NoteName::new(C, Natural), ScaleMode::Major);
+        let five_of_five = RomanNumeral::new(c_major, chord(D, Natural, Dominant7));
+        assert_eq!(five_of_five.degree(), 1);
+        assert_eq!(five_of_five.alteration(), Natural);
+        let seven_of_five = RomanNumeral::new(c_major, chord(F, Sharp, Diminished7));
+        assert_eq!(format!("{seven_of_five}"), "vii°7/V");
+        assert_eq!(seven_of_five.degree(), 3);
+        assert_eq!(seven_of_five.alteration(), Sharp);
+    }
+}
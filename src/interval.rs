@@ -0,0 +1,216 @@
+use std::fmt::Display;
+
+use crate::Pitch;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum IntervalQuality {
+    Diminished,
+    Minor,
+    Perfect,
+    Major,
+    Augmented,
+}
+
+impl IntervalQuality {
+    pub fn abbreviation(&self) -> char {
+        match self {
+            IntervalQuality::Diminished => 'd',
+            IntervalQuality::Minor => 'm',
+            IntervalQuality::Perfect => 'P',
+            IntervalQuality::Major => 'M',
+            IntervalQuality::Augmented => 'A',
+        }
+    }
+}
+
+/// A named interval such as a minor 3rd or a perfect 5th. `number` counts
+/// letter names inclusively, so a unison is 1 and an octave 8; numbers above
+/// 8 are compound intervals.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Interval {
+    quality: IntervalQuality,
+    number: u8,
+}
+
+impl Interval {
+    /// `None` if `quality` cannot go with `number`, such as a major 5th.
+    pub fn new(quality: IntervalQuality, number: u8) -> Option<Self> {
+        let perfect = is_perfect_class(number);
+        let valid = match quality {
+            IntervalQuality::Perfect => perfect,
+            IntervalQuality::Major | IntervalQuality::Minor => !perfect,
+            IntervalQuality::Diminished => number > 1,
+            IntervalQuality::Augmented => true,
+        };
+        if number > 0 && valid {
+            Some(Self { quality, number })
+        } else {
+            None
+        }
+    }
+
+    /// The usual name for an interval of `semitones`, calling the tritone
+    /// an augmented 4th.
+    pub fn from_semitones(semitones: u8) -> Self {
+        let (quality, number) = match semitones % 12 {
+            0 => (IntervalQuality::Perfect, 1),
+            1 => (IntervalQuality::Minor, 2),
+            2 => (IntervalQuality::Major, 2),
+            3 => (IntervalQuality::Minor, 3),
+            4 => (IntervalQuality::Major, 3),
+            5 => (IntervalQuality::Perfect, 4),
+            6 => (IntervalQuality::Augmented, 4),
+            7 => (IntervalQuality::Perfect, 5),
+            8 => (IntervalQuality::Minor, 6),
+            9 => (IntervalQuality::Major, 6),
+            10 => (IntervalQuality::Minor, 7),
+            _ => (IntervalQuality::Major, 7),
+        };
+        Self {
+            quality,
+            number: number + 7 * (semitones / 12),
+        }
+    }
+
+    /// The interval from the lower of `a` and `b` to the higher, named by
+    /// their spellings, so C to F♯ is an augmented 4th but C to G♭ a
+    /// diminished 5th. `None` for doubly augmented or diminished intervals.
+    pub fn between(a: Pitch, b: Pitch) -> Option<Self> {
+        let (low, high) = if diatonic_position(a) <= diatonic_position(b) {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        let number = (diatonic_position(high) - diatonic_position(low) + 1) as u8;
+        let octaves = (number as i16 - 1) / 7;
+        let difference = chromatic_position(high)
+            - chromatic_position(low)
+            - 12 * octaves
+            - reference_semitones(number) as i16;
+        let quality = match (is_perfect_class(number), difference) {
+            (true, 0) => IntervalQuality::Perfect,
+            (true, -1) => IntervalQuality::Diminished,
+            (true, 1) => IntervalQuality::Augmented,
+            (false, 0) => IntervalQuality::Major,
+            (false, -1) => IntervalQuality::Minor,
+            (false, -2) => IntervalQuality::Diminished,
+            (false, 1) => IntervalQuality::Augmented,
+            _ => return None,
+        };
+        Self::new(quality, number)
+    }
+
+    pub fn quality(&self) -> IntervalQuality {
+        self.quality
+    }
+
+    pub fn number(&self) -> u8 {
+        self.number
+    }
+
+    pub fn semitones(&self) -> u8 {
+        let octaves = (self.number - 1) / 7;
+        let reference = reference_semitones(self.number) + 12 * octaves;
+        let offset: i8 = match (is_perfect_class(self.number), self.quality) {
+            (_, IntervalQuality::Augmented) => 1,
+            (true, IntervalQuality::Diminished) => -1,
+            (false, IntervalQuality::Diminished) => -2,
+            (_, IntervalQuality::Minor) => -1,
+            _ => 0,
+        };
+        reference.saturating_add_signed(offset)
+    }
+
+    /// The name written out, as in "minor 3rd" or "perfect octave".
+    pub fn name(&self) -> String {
+        let quality = match self.quality {
+            IntervalQuality::Diminished => "diminished",
+            IntervalQuality::Minor => "minor",
+            IntervalQuality::Perfect => "perfect",
+            IntervalQuality::Major => "major",
+            IntervalQuality::Augmented => "augmented",
+        };
+        let number = match self.number {
+            1 => "unison".to_string(),
+            8 => "octave".to_string(),
+            n => {
+                let suffix = match (n % 10, n % 100) {
+                    (_, 11..=13) => "th",
+                    (1, _) => "st",
+                    (2, _) => "nd",
+                    (3, _) => "rd",
+                    _ => "th",
+                };
+                format!("{n}{suffix}")
+            }
+        };
+        format!("{quality} {number}")
+    }
+}
+
+/// Writes the abbreviation, as in "m3", "P5" or "A4".
+impl Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.quality.abbreviation(), self.number)
+    }
+}
+
+fn is_perfect_class(number: u8) -> bool {
+    matches!(number.saturating_sub(1) % 7, 0 | 3 | 4)
+}
+
+/// Semitones in the perfect or major interval of this simple number.
+fn reference_semitones(number: u8) -> u8 {
+    [0, 2, 4, 5, 7, 9, 11][(number.saturating_sub(1) % 7) as usize]
+}
+
+fn diatonic_position(pitch: Pitch) -> i16 {
+    pitch.octave() as i16 * 7 + pitch.name().letter().index() as i16
+}
+
+fn chromatic_position(pitch: Pitch) -> i16 {
+    pitch.octave() as i16 * 12
+        + pitch.name().letter().natural_pitch() as i16
+        + pitch.name().modifier().semitones() as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interval::{Interval, IntervalQuality};
+    use crate::Pitch;
+
+    #[test]
+    fn test_intervals() {
+        for (low, high, expected, name) in [
+            ("C4", "E♭4", "m3", "minor 3rd"),
+            ("C4", "G4", "P5", "perfect 5th"),
+            ("C4", "F#4", "A4", "augmented 4th"),
+            ("C4", "Gb4", "d5", "diminished 5th"),
+            ("B3", "C4", "m2", "minor 2nd"),
+            ("C4", "C5", "P8", "perfect octave"),
+            ("C4", "D5", "M9", "major 9th"),
+            ("E4", "E4", "P1", "perfect unison"),
+            ("C#4", "Bb4", "d7", "diminished 7th"),
+        ] {
+            let low = low.parse::<Pitch>().unwrap();
+            let high = high.parse::<Pitch>().unwrap();
+            let interval = Interval::between(low, high).unwrap();
+            assert_eq!(interval.to_string(), expected);
+            assert_eq!(interval.name(), name);
+            assert_eq!(Interval::between(high, low), Some(interval));
+            assert_eq!(
+                interval.semitones() as i16,
+                high.midi().unwrap() as i16 - low.midi().unwrap() as i16
+            );
+        }
+        assert_eq!(Interval::from_semitones(7).to_string(), "P5");
+        assert_eq!(Interval::from_semitones(15).to_string(), "m10");
+        assert_eq!(Interval::new(IntervalQuality::Major, 5), None);
+        assert_eq!(
+            Interval::new(IntervalQuality::Perfect, 11)
+                .unwrap()
+                .semitones(),
+            17
+        );
+    }
+}
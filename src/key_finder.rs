@@ -0,0 +1,323 @@
+use enum_iterator::all;
+use midi_note_recorder::Recording;
+
+use crate::{
+    correlation,
+    harmony::{HarmonicFunction, RomanNumeral},
+    Chord, Key, NoteName, PitchSequence, ScaleMode,
+};
+
+/// Krumhansl-Kessler probe-tone ratings, indexed by semitones above the
+/// tonic.
+const MAJOR_PROFILE: [f64; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const MINOR_PROFILE: [f64; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+/// Rating for pitch classes outside the scale in the profiles derived for
+/// the other modes; roughly the Krumhansl-Kessler average for such tones.
+const NON_SCALE_RATING: f64 = 2.4;
+
+/// How much better, in correlation, a new key must fit a window than the
+/// current key before `key_regions` reports a modulation.
+const KEY_CHANGE_MARGIN: f64 = 0.05;
+
+pub fn keys_from(recording: &Recording) -> Vec<(Key, f64)> {
+    PitchSequence::new(recording).keys()
+}
+
+/// Every tonic and tonal `ScaleMode`, ranked by the correlation of its
+/// key profile with the time each pitch class sounds. Best first.
+pub fn ranked_keys(pitch_class_durations: &[f64; 12]) -> Vec<(Key, f64)> {
+    let modes = all::<ScaleMode>()
+        .filter(|m| m.is_tonal())
+        .collect::<Vec<_>>();
+    ranked_keys_in(pitch_class_durations, &modes)
+}
+
+/// As `ranked_keys`, considering only the given modes.
+pub fn ranked_keys_in(pitch_class_durations: &[f64; 12], modes: &[ScaleMode]) -> Vec<(Key, f64)> {
+    let mut result = vec![];
+    for mode in modes.iter().copied() {
+        let profile = profile(mode);
+        for tonic in 0..12 {
+            let rotated = (0..12)
+                .map(|pc| profile[(pc + 12 - tonic) % 12])
+                .collect::<Vec<_>>();
+            result.push((
+                Key::from_pitch_class(tonic as u8, mode),
+                correlation(pitch_class_durations, &rotated),
+            ));
+        }
+    }
+    result.sort_by(|(_, c1), (_, c2)| c2.total_cmp(c1));
+    result
+}
+
+/// A stretch of a piece that stays in one key. `pivots` holds the chords
+/// leading into the region that belong to both the previous key and this one.
+#[derive(Clone, Debug)]
+pub struct KeyRegion {
+    key: Key,
+    start: f64,
+    end: f64,
+    pivots: Vec<PivotChord>,
+}
+
+impl KeyRegion {
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    pub fn start(&self) -> f64 {
+        self.start
+    }
+
+    pub fn end(&self) -> f64 {
+        self.end
+    }
+
+    pub fn pivots(&self) -> &[PivotChord] {
+        &self.pivots
+    }
+}
+
+/// A chord heard at `start` that is diatonic in both keys around a
+/// modulation, with its Roman numeral before and after the change.
+#[derive(Copy, Clone, Debug)]
+pub struct PivotChord {
+    chord: Chord,
+    start: f64,
+    before: RomanNumeral,
+    after: RomanNumeral,
+}
+
+impl PivotChord {
+    pub fn chord(&self) -> Chord {
+        self.chord
+    }
+
+    pub fn start(&self) -> f64 {
+        self.start
+    }
+
+    pub fn before(&self) -> RomanNumeral {
+        self.before
+    }
+
+    pub fn after(&self) -> RomanNumeral {
+        self.after
+    }
+}
+
+/// Splits `seq` into regions of constant key. Each analysis window of
+/// `window` seconds, stepped by `hop`, is ranked against keys in `modes`.
+/// The key changes only when another key beats the current one by
+/// `KEY_CHANGE_MARGIN`, and regions shorter than a window are absorbed into
+/// the region that follows them. Empty if `modes` is empty or `window` or
+/// `hop` is not positive.
+pub fn key_regions(
+    seq: &PitchSequence,
+    modes: &[ScaleMode],
+    window: f64,
+    hop: f64,
+) -> Vec<KeyRegion> {
+    if modes.is_empty() || window <= 0.0 || hop <= 0.0 {
+        return vec![];
+    }
+    let end = seq.end_time();
+    let mut changes: Vec<(Key, f64)> = vec![];
+    let mut start = 0.0;
+    loop {
+        let keys = ranked_keys_in(
+            &seq.pitch_class_durations_between(start, start + window),
+            modes,
+        );
+        let (best, best_fit) = keys[0];
+        match changes.last() {
+            None => changes.push((best, 0.0)),
+            Some((current, _)) => {
+                let current_fit = keys.iter().find(|(k, _)| k == current).unwrap().1;
+                if best != *current && best_fit - current_fit > KEY_CHANGE_MARGIN {
+                    changes.push((best, start));
+                }
+            }
+        }
+        if start + window >= end {
+            break;
+        }
+        start += hop;
+    }
+
+    let mut merged: Vec<(Key, f64, f64)> = vec![];
+    for (i, (key, start)) in changes.iter().enumerate() {
+        let region_end = changes.get(i + 1).map_or(end, |(_, t)| *t);
+        let mut region_start = *start;
+        if let Some((_, previous_start, previous_end)) = merged.last() {
+            if previous_end - previous_start < window {
+                region_start = *previous_start;
+                merged.pop();
+            }
+        }
+        match merged.last_mut() {
+            Some((previous_key, _, previous_end)) if previous_key == key => {
+                *previous_end = region_end
+            }
+            _ => merged.push((*key, region_start, region_end)),
+        }
+    }
+
+    let chords = seq.chords_starts_durations();
+    let mut result: Vec<KeyRegion> = vec![];
+    for (key, start, end) in merged {
+        let pivots = result.last().map_or(vec![], |previous| {
+            pivot_chords(&chords, previous.key, key, start, window)
+        });
+        result.push(KeyRegion {
+            key,
+            start,
+            end,
+            pivots,
+        });
+    }
+    result
+}
+
+/// Finds the first chord near `boundary` that belongs to `to` but not to
+/// `from`, and returns the run of chords just before it that belong to both.
+fn pivot_chords(
+    chords: &[(Chord, f64, f64)],
+    from: Key,
+    to: Key,
+    boundary: f64,
+    window: f64,
+) -> Vec<PivotChord> {
+    let nearby = chords
+        .iter()
+        .filter(|(_, start, _)| (boundary - window..boundary + window).contains(start))
+        .collect::<Vec<_>>();
+    let Some(arrival) = nearby
+        .iter()
+        .position(|(chord, _, _)| !is_diatonic(from, chord) && is_diatonic(to, chord))
+    else {
+        return vec![];
+    };
+    let mut result = nearby[..arrival]
+        .iter()
+        .rev()
+        .take_while(|(chord, _, _)| is_diatonic(from, chord) && is_diatonic(to, chord))
+        .map(|(chord, start, _)| PivotChord {
+            chord: *chord,
+            start: *start,
+            before: RomanNumeral::new(from, chord.name()),
+            after: RomanNumeral::new(to, chord.name()),
+        })
+        .collect::<Vec<_>>();
+    result.reverse();
+    result
+}
+
+fn is_diatonic(key: Key, chord: &Chord) -> bool {
+    RomanNumeral::new(key, chord.name()).function() == HarmonicFunction::Diatonic
+}
+
+/// Major and minor use the Krumhansl-Kessler profiles directly. Each other
+/// mode gives its scale degrees the ratings of the same degrees of the major
+/// or natural minor scale, whichever shares its third.
+fn profile(mode: ScaleMode) -> [f64; 12] {
+    match mode {
+        ScaleMode::Major => MAJOR_PROFILE,
+        ScaleMode::Minor => MINOR_PROFILE,
+        _ => {
+            let c = NoteName::name_of(0);
+            let scale = mode.pitch_classes(c);
+            let (source, parent) = if scale.contains(&3) {
+                (MINOR_PROFILE, ScaleMode::Minor)
+            } else {
+                (MAJOR_PROFILE, ScaleMode::Major)
+            };
+            let mut result = [NON_SCALE_RATING; 12];
+            for (pc, parent_pc) in scale.iter().zip(parent.pitch_classes(c)) {
+                result[*pc as usize] = source[parent_pc as usize];
+            }
+            result
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use midi_msg::Channel;
+    use midi_note_recorder::{midi_msg_from, Recording};
+
+    use crate::key_finder::{key_regions, keys_from};
+    use crate::{ActivePitches, PitchSequence, ScaleMode};
+
+    #[test]
+    fn test_keys_from() {
+        for (filename, expected) in [("healing4", "E  Major"), ("up_down", "C  Major")] {
+            let recording = Recording::from_file(filename).unwrap();
+            let keys = keys_from(&recording);
+            assert_eq!(format!("{}", keys[0].0), expected);
+            assert!(keys.windows(2).all(|w| w[0].1 >= w[1].1));
+        }
+    }
+
+    #[test]
+    fn test_key_regions() {
+        let c = vec![48, 52, 55];
+        let f = vec![53, 57, 60];
+        let g7 = vec![55, 59, 62, 65];
+        let am = vec![57, 60, 64];
+        let d7 = vec![50, 54, 57, 60];
+        let g = vec![55, 59, 62];
+        let em = vec![52, 55, 59];
+        let progression = [
+            &c, &f, &g7, &c, &c, &f, &g7, &c, &am, &d7, &g, &em, &c, &d7, &g, &g,
+        ];
+        let mut seq = PitchSequence::default();
+        let mut current = ActivePitches::default();
+        for (i, chord) in progression.iter().enumerate() {
+            let start = 2.0 * i as f64;
+            for pitch in chord.iter() {
+                seq.push(
+                    start,
+                    &midi_msg_from(Channel::Ch1, *pitch, 100),
+                    &mut current,
+                );
+            }
+            for pitch in chord.iter() {
+                seq.push(
+                    start + 1.9,
+                    &midi_msg_from(Channel::Ch1, *pitch, 0),
+                    &mut current,
+                );
+            }
+        }
+
+        let regions = key_regions(&seq, &[ScaleMode::Major, ScaleMode::Minor], 8.0, 2.0);
+        assert_eq!(regions.len(), 2);
+        assert_eq!(format!("{}", regions[0].key()), "C  Major");
+        assert_eq!(format!("{}", regions[1].key()), "G  Major");
+        assert_eq!(regions[1].start(), 16.0);
+        assert_eq!(regions[1].end(), seq.end_time());
+        let pivots = regions[1]
+            .pivots()
+            .iter()
+            .map(|p| format!("{} {} {}", p.chord().name(), p.before(), p.after()))
+            .collect::<Vec<_>>();
+        assert_eq!(pivots, vec!["C  Major I IV", "A  Minor vi ii"]);
+
+        for (modes, window, hop) in [
+            (&[][..], 8.0, 2.0),
+            (&[ScaleMode::Major][..], 0.0, 2.0),
+            (&[ScaleMode::Major][..], -8.0, 2.0),
+            (&[ScaleMode::Major][..], 8.0, 0.0),
+            (&[ScaleMode::Major][..], 8.0, -2.0),
+        ] {
+            assert!(key_regions(&seq, modes, window, hop).is_empty());
+        }
+    }
+}
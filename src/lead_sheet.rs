@@ -0,0 +1,362 @@
+//! Lead sheets in LilyPond and ABC notation: a melody on one staff with
+//! chord names above it, quantized and spelled as for the MusicXML writer.
+
+use crate::notation::{Alterations, NoteLength, Score, Segment};
+use crate::{Accidental, Chord, ChordMode, Key, NoteLetter, NoteName, Pitch, ScaleMode};
+
+/// LilyPond source for `melody`, in the form returned by
+/// `durations_notes_from`, under the chord names of `chords`. The melody
+/// begins at time `start` on the clock of `chords`, which for a recording is
+/// the time of its first note. Measures have `beats_per_measure` quarter
+/// notes.
+pub fn lilypond_from(
+    melody: &[(f64, u8, u8)],
+    start: f64,
+    chords: &[(Chord, f64, f64)],
+    key: Key,
+    beats_per_minute: f64,
+    beats_per_measure: u8,
+) -> String {
+    let score = Score::from_melody(
+        melody,
+        start,
+        chords,
+        key,
+        beats_per_minute,
+        beats_per_measure,
+    );
+    let mut chord_lines = vec![];
+    let mut staff_lines = vec![];
+    for measure in score.measures.iter() {
+        let mut chord_line = vec![];
+        let mut staff_line = vec![];
+        for segment in measure.iter() {
+            let lengths = segment.lengths();
+            for (i, length) in lengths.iter().enumerate() {
+                let duration = lilypond_duration(length);
+                chord_line.push(match segment.chord {
+                    Some(chord) if i == 0 => format!("{}{duration}", lilypond_chord(&chord)),
+                    _ => format!("s{duration}"),
+                });
+                staff_line.push(if segment.pitches.is_empty() {
+                    format!("r{duration}")
+                } else {
+                    let pitches = segment
+                        .pitches
+                        .iter()
+                        .map(|p| {
+                            let tie = if ties_out(segment, &lengths, i, *p) {
+                                "~"
+                            } else {
+                                ""
+                            };
+                            (lilypond_pitch(score.key.spell_pitch(*p)), tie)
+                        })
+                        .collect::<Vec<_>>();
+                    if let [(pitch, tie)] = &pitches[..] {
+                        format!("{pitch}{duration}{tie}")
+                    } else {
+                        let pitches = pitches
+                            .iter()
+                            .map(|(pitch, tie)| format!("{pitch}{tie}"))
+                            .collect::<Vec<_>>();
+                        format!("<{}>{duration}", pitches.join(" "))
+                    }
+                });
+            }
+        }
+        chord_lines.push(format!("      {} |", chord_line.join(" ")));
+        staff_lines.push(format!("      {} |", staff_line.join(" ")));
+    }
+
+    let signature = score.signature_key;
+    format!(
+        "\\version \"2.24.0\"\n\\score {{\n  <<\n    \\new ChordNames \\chordmode {{\n{}\n    }}\n    \\new Staff {{\n      \\clef {}\n      \\key {} \\{}\n      \\time {}/4\n      \\tempo 4 = {}\n{}\n      \\bar \"|.\"\n    }}\n  >>\n  \\layout {{ }}\n}}\n",
+        chord_lines.join("\n"),
+        if score.is_bass() { "bass" } else { "treble" },
+        lilypond_name(signature.tonic()),
+        format!("{:?}", signature.mode()).to_lowercase(),
+        score.beats_per_measure,
+        score.beats_per_minute.round(),
+        staff_lines.join("\n")
+    )
+}
+
+/// ABC notation for `melody`, with the same arguments as `lilypond_from`.
+/// Chord symbols are written in ASCII, as in "Bbm7b5".
+pub fn abc_from(
+    melody: &[(f64, u8, u8)],
+    start: f64,
+    chords: &[(Chord, f64, f64)],
+    key: Key,
+    beats_per_minute: f64,
+    beats_per_measure: u8,
+) -> String {
+    let score = Score::from_melody(
+        melody,
+        start,
+        chords,
+        key,
+        beats_per_minute,
+        beats_per_measure,
+    );
+    let signature = score.signature_key;
+    let mut result = format!(
+        "X:1\nM:{}/4\nL:1/16\nQ:1/4={}\nK:{}{}{}\n",
+        score.beats_per_measure,
+        score.beats_per_minute.round(),
+        ascii_name(signature.tonic()),
+        abc_mode(signature.mode()),
+        if score.is_bass() { " clef=bass" } else { "" }
+    );
+    for (m, measure) in score.measures.iter().enumerate() {
+        let mut alterations = Alterations::new(signature);
+        let mut tokens = vec![];
+        for segment in measure.iter() {
+            let lengths = segment.lengths();
+            for (i, length) in lengths.iter().enumerate() {
+                let mut token = String::new();
+                if let (Some(chord), 0) = (segment.chord, i) {
+                    token.push_str(&format!("\"{}\"", ascii_symbol(&chord)));
+                }
+                let duration = match length.sixteenths {
+                    1 => String::new(),
+                    n => n.to_string(),
+                };
+                let pitches = segment
+                    .pitches
+                    .iter()
+                    .map(|p| {
+                        let spelled = score.key.spell_pitch(*p);
+                        let tied_in = i > 0 || segment.tied_in.contains(p);
+                        let accidental = if !tied_in && alterations.needs_accidental(spelled) {
+                            abc_accidental(spelled.name().modifier())
+                        } else {
+                            ""
+                        };
+                        let tie = if ties_out(segment, &lengths, i, *p) {
+                            "-"
+                        } else {
+                            ""
+                        };
+                        format!("{accidental}{}{tie}", abc_pitch(spelled))
+                    })
+                    .collect::<Vec<_>>();
+                match pitches.len() {
+                    0 => token.push_str(&format!("z{duration}")),
+                    1 => {
+                        let pitch = &pitches[0];
+                        match pitch.strip_suffix('-') {
+                            Some(pitch) => token.push_str(&format!("{pitch}{duration}-")),
+                            None => token.push_str(&format!("{pitch}{duration}")),
+                        }
+                    }
+                    _ => token.push_str(&format!("[{}]{duration}", pitches.concat())),
+                }
+                tokens.push(token);
+            }
+        }
+        result.push_str(&tokens.join(" "));
+        if m + 1 == score.measures.len() {
+            result.push_str(" |]\n");
+        } else if m % 4 == 3 {
+            result.push_str(" |\n");
+        } else {
+            result.push_str(" | ");
+        }
+    }
+    result
+}
+
+fn ties_out(segment: &Segment, lengths: &[NoteLength], i: usize, pitch: u8) -> bool {
+    i + 1 < lengths.len() || segment.tied_out.contains(&pitch)
+}
+
+fn lilypond_duration(length: &NoteLength) -> String {
+    format!(
+        "{}{}",
+        length.denominator,
+        if length.dotted { "." } else { "" }
+    )
+}
+
+/// Dutch note names, LilyPond's default: "cis" for C♯, "es" for E♭.
+fn lilypond_name(name: NoteName) -> String {
+    let letter = format!("{:?}", name.letter()).to_lowercase();
+    let suffix = match name.modifier() {
+        Accidental::DoubleFlat => "eses",
+        Accidental::Flat => "es",
+        Accidental::Natural => "",
+        Accidental::Sharp => "is",
+        Accidental::DoubleSharp => "isis",
+    };
+    match (name.letter(), name.modifier()) {
+        (NoteLetter::E | NoteLetter::A, Accidental::Flat | Accidental::DoubleFlat) => {
+            format!("{letter}{}", &suffix[1..])
+        }
+        _ => format!("{letter}{suffix}"),
+    }
+}
+
+/// In absolute octave entry, where c is the C below middle C.
+fn lilypond_pitch(pitch: Pitch) -> String {
+    let octave = pitch.octave() as i32 - 3;
+    let marks = if octave >= 0 {
+        "'".repeat(octave as usize)
+    } else {
+        ",".repeat(-octave as usize)
+    };
+    format!("{}{marks}", lilypond_name(pitch.name()))
+}
+
+fn lilypond_chord(chord: &Chord) -> String {
+    let name = chord.name();
+    let mut result = lilypond_name(name.root());
+    let modifier = match name.mode() {
+        ChordMode::Major => "",
+        ChordMode::Minor => "m",
+        ChordMode::Diminished => "dim",
+        ChordMode::Augmented => "aug",
+        ChordMode::Dominant7 => "7",
+        ChordMode::Major7 => "maj7",
+        ChordMode::Minor7 => "m7",
+        ChordMode::HalfDiminished7 => "m7.5-",
+        ChordMode::Diminished7 => "dim7",
+        ChordMode::MinorMajor7 => "m7+",
+        ChordMode::Major6 => "6",
+        ChordMode::Minor6 => "m6",
+        ChordMode::Dominant9 => "9",
+        ChordMode::Major9 => "maj9",
+        ChordMode::Minor9 => "m9",
+        ChordMode::Dominant11 => "11",
+        ChordMode::Dominant13 => "13",
+        ChordMode::Dominant7Sus4 => "7sus4",
+        ChordMode::Sus4 => "sus4",
+        ChordMode::Sus2 => "sus2",
+        ChordMode::Add9 => "9^7",
+        ChordMode::MinorAdd9 => "m9^7",
+        ChordMode::Power => "1.5",
+    };
+    if !modifier.is_empty() {
+        result.push(':');
+        result.push_str(modifier);
+    }
+    if chord.bass() % 12 != name.root().pitch_class() {
+        result.push('/');
+        result.push_str(&lilypond_name(chord.bass_name()));
+    }
+    result
+}
+
+fn abc_mode(mode: ScaleMode) -> &'static str {
+    match mode {
+        ScaleMode::Minor => "m",
+        ScaleMode::Dorian => "Dor",
+        ScaleMode::Phrygian => "Phr",
+        ScaleMode::Lydian => "Lyd",
+        ScaleMode::Mixolydian => "Mix",
+        ScaleMode::Locrian => "Loc",
+        _ => "",
+    }
+}
+
+fn abc_accidental(accidental: Accidental) -> &'static str {
+    match accidental {
+        Accidental::DoubleFlat => "__",
+        Accidental::Flat => "_",
+        Accidental::Natural => "=",
+        Accidental::Sharp => "^",
+        Accidental::DoubleSharp => "^^",
+    }
+}
+
+/// Capitals from middle C up to the B above it, lower case for the octave
+/// above that, with commas and apostrophes for lower and higher octaves.
+fn abc_pitch(pitch: Pitch) -> String {
+    let letter = format!("{:?}", pitch.name().letter());
+    if pitch.octave() >= 5 {
+        format!(
+            "{}{}",
+            letter.to_lowercase(),
+            "'".repeat((pitch.octave() - 5) as usize)
+        )
+    } else {
+        format!("{letter}{}", ",".repeat((4 - pitch.octave()) as usize))
+    }
+}
+
+fn ascii_name(name: NoteName) -> String {
+    let accidental = match name.modifier() {
+        Accidental::DoubleFlat => "bb",
+        Accidental::Flat => "b",
+        Accidental::Natural => "",
+        Accidental::Sharp => "#",
+        Accidental::DoubleSharp => "##",
+    };
+    format!("{:?}{accidental}", name.letter())
+}
+
+fn ascii_symbol(chord: &Chord) -> String {
+    let name = chord.name();
+    let mut result = format!(
+        "{}{}",
+        ascii_name(name.root()),
+        name.mode().symbol().replace('\u{266d}', "b")
+    );
+    if chord.bass() % 12 != name.root().pitch_class() {
+        result.push('/');
+        result.push_str(&ascii_name(chord.bass_name()));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use midi_msg::Channel;
+    use midi_note_recorder::midi_msg_from;
+
+    use crate::lead_sheet::{abc_from, lilypond_from};
+    use crate::{ActivePitches, Chord, ChordName, Key, ScaleMode};
+
+    fn c_major_chord() -> Chord {
+        let mut triad = ActivePitches::default();
+        for pitch in [48, 52, 55] {
+            triad.update_from(&midi_msg_from(Channel::Ch1, pitch, 100));
+        }
+        Chord {
+            name: ChordName::new(triad).unwrap(),
+            notes: triad,
+        }
+    }
+
+    #[test]
+    fn test_lead_sheets() {
+        // Quarter notes last half a second: C, E♭, a rest, then a G held
+        // across the barline.
+        let melody = [
+            (0.5, 60, 100),
+            (0.0, 60, 0),
+            (0.5, 63, 100),
+            (0.5, 63, 0),
+            (1.5, 67, 100),
+            (0.0, 67, 0),
+        ];
+        let chords = [(c_major_chord(), 0.0, 3.0)];
+        let c_major = Key::from_pitch_class(0, ScaleMode::Major);
+
+        let lilypond = lilypond_from(&melody, 0.0, &chords, c_major, 120.0, 4);
+        assert!(lilypond.contains("\\key c \\major"));
+        assert!(lilypond.contains("\\time 4/4"));
+        assert!(lilypond.contains("      c4 s4 s4 s4 |\n      s2 s2 |"));
+        assert!(lilypond.contains("      c'4 es'4 r4 g'4~ |\n      g'2 r2 |"));
+
+        let abc = abc_from(&melody, 0.0, &chords, c_major, 120.0, 4);
+        assert!(abc.contains("M:4/4\nL:1/16\nQ:1/4=120\nK:C\n"));
+        assert!(abc.ends_with("\"C\"C4 _E4 z4 G4- | G8 z8 |]\n"));
+
+        let c_minor = Key::from_pitch_class(0, ScaleMode::Minor);
+        let abc = abc_from(&melody, 0.0, &chords, c_minor, 120.0, 3);
+        assert!(abc.contains("K:Cm\n"));
+        assert!(abc.ends_with("\"C\"C4 E4 z4 | G12 |]\n"));
+    }
+}
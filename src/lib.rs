@@ -1,10 +1,30 @@
+pub mod beat_tracker;
 pub mod generator;
-
-use std::{collections::VecDeque, fmt::Display};
-
-use enum_iterator::Sequence;
-use midi_msg::MidiMsg;
-use midi_note_recorder::{note_velocity_from, Recording};
+pub mod harmony;
+pub mod interval;
+pub mod key_finder;
+pub mod lead_sheet;
+pub mod meter_finder;
+pub mod musicxml;
+mod notation;
+mod parse;
+pub mod quantizer;
+pub mod scale_finder;
+pub mod smf;
+pub mod voice_separator;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+};
+
+use beat_tracker::BeatGrid;
+use enum_iterator::{all, cardinality, Sequence};
+use interval::Interval;
+use meter_finder::Meter;
+use midi_msg::{Channel, ChannelVoiceMsg, MidiMsg};
+use midi_note_recorder::{midi_msg_from, note_velocity_from, Recording};
+use quantizer::{Grid, QuantizationError};
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Sequence)]
 pub enum NoteLetter {
@@ -26,6 +46,11 @@ impl NoteLetter {
         enum_iterator::previous_cycle(self)
     }
 
+    /// Position from C, so C is 0 and B is 6.
+    pub fn index(&self) -> usize {
+        all::<NoteLetter>().position(|l| l == *self).unwrap()
+    }
+
     pub fn natural_pitch(&self) -> u8 {
         match self {
             NoteLetter::C => 0,
@@ -71,40 +96,49 @@ const MINOR_ROOT_IDS: [(NoteLetter, Accidental); 12] = [
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum Accidental {
+    DoubleFlat,
     Flat,
     Natural,
     Sharp,
+    DoubleSharp,
 }
 
 impl Accidental {
+    pub fn from_semitones(semitones: i8) -> Option<Self> {
+        match semitones {
+            -2 => Some(Accidental::DoubleFlat),
+            -1 => Some(Accidental::Flat),
+            0 => Some(Accidental::Natural),
+            1 => Some(Accidental::Sharp),
+            2 => Some(Accidental::DoubleSharp),
+            _ => None,
+        }
+    }
+
     pub fn symbol(&self) -> char {
         match self {
+            Accidental::DoubleFlat => '\u{1d12b}',
             Accidental::Flat => '\u{266d}',
             //Accidental::Natural => '\u{266e}',
             Accidental::Natural => ' ',
             Accidental::Sharp => '\u{266f}',
+            Accidental::DoubleSharp => '\u{1d12a}',
         }
     }
 
-    pub fn pitch_shift(&self, natural: u8) -> Option<u8> {
+    pub fn semitones(&self) -> i8 {
         match self {
-            Accidental::Flat => {
-                if natural > 0 {
-                    Some(natural - 1)
-                } else {
-                    None
-                }
-            }
-            Accidental::Natural => Some(natural),
-            Accidental::Sharp => {
-                if natural < u8::MAX {
-                    Some(natural + 1)
-                } else {
-                    None
-                }
-            }
+            Accidental::DoubleFlat => -2,
+            Accidental::Flat => -1,
+            Accidental::Natural => 0,
+            Accidental::Sharp => 1,
+            Accidental::DoubleSharp => 2,
         }
     }
+
+    pub fn pitch_shift(&self, natural: u8) -> Option<u8> {
+        natural.checked_add_signed(self.semitones())
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -114,15 +148,59 @@ pub struct NoteName {
 }
 
 impl NoteName {
+    pub fn new(letter: NoteLetter, modifier: Accidental) -> Self {
+        Self { letter, modifier }
+    }
+
+    pub fn letter(&self) -> NoteLetter {
+        self.letter
+    }
+
+    pub fn modifier(&self) -> Accidental {
+        self.modifier
+    }
+
     pub fn name_of(pitch: u8) -> Self {
         let (letter, modifier) = MAJOR_ROOT_IDS[(pitch % 12) as usize];
         Self { letter, modifier }
     }
 
+    /// Wraps into the lowest octave, so C♭ and C𝄫 give 11 and 10.
     pub fn lowest_midi_note(&self) -> u8 {
+        self.pitch_class()
+    }
+
+    pub fn pitch_class(&self) -> u8 {
         self.modifier
-            .pitch_shift(self.letter.natural_pitch())
+            .pitch_shift(self.letter.natural_pitch() + 12)
             .unwrap()
+            % 12
+    }
+
+    /// The note `semitones` above this one, spelled with the letter
+    /// `letter_steps` above this one's letter. Returns `None` if that spelling
+    /// would need more than a double sharp or flat.
+    pub fn spelled_up(&self, semitones: u8, letter_steps: usize) -> Option<Self> {
+        let mut letter = self.letter;
+        for _ in 0..letter_steps {
+            letter = letter.next();
+        }
+        let target = (self.pitch_class() + semitones) % 12;
+        Self::with_letter(letter, target)
+    }
+
+    /// Spells `pitch` on `letter`, or `None` if that needs more than a
+    /// double sharp or flat.
+    pub fn with_letter(letter: NoteLetter, pitch: u8) -> Option<Self> {
+        let shift = ((pitch % 12 + 12 - letter.natural_pitch()) % 12) as i8;
+        let shift = if shift > 6 { shift - 12 } else { shift };
+        Accidental::from_semitones(shift).map(|modifier| Self { letter, modifier })
+    }
+
+    /// The same letter, raised (or lowered, if negative) by `semitones`.
+    pub fn altered(&self, semitones: i8) -> Option<Self> {
+        Accidental::from_semitones(self.modifier.semitones() + semitones)
+            .map(|modifier| Self::new(self.letter, modifier))
     }
 }
 
@@ -132,23 +210,154 @@ impl Display for NoteName {
     }
 }
 
+/// A note name with an octave in scientific pitch notation, where middle C
+/// (MIDI 60) is C4. The octave goes with the letter, so B♯3 and C4 are both
+/// MIDI 60.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Pitch {
+    name: NoteName,
+    octave: i8,
+}
+
+impl Pitch {
+    pub fn new(name: NoteName, octave: i8) -> Self {
+        Self { name, octave }
+    }
+
+    pub fn from_midi(pitch: u8) -> Self {
+        Self {
+            name: NoteName::name_of(pitch),
+            octave: (pitch / 12) as i8 - 1,
+        }
+    }
+
+    /// Spells MIDI note `pitch` as `name`, choosing the octave that keeps
+    /// the pitch unchanged, so B♯3 and C♭5 can be written. `None` if `name`
+    /// is not an enharmonic of `pitch`.
+    pub fn spelled(pitch: u8, name: NoteName) -> Option<Self> {
+        if name.pitch_class() != pitch % 12 {
+            return None;
+        }
+        let natural =
+            pitch as i16 - name.letter.natural_pitch() as i16 - name.modifier.semitones() as i16;
+        Some(Self {
+            name,
+            octave: (natural.div_euclid(12) - 1) as i8,
+        })
+    }
+
+    pub fn name(&self) -> NoteName {
+        self.name
+    }
+
+    pub fn octave(&self) -> i8 {
+        self.octave
+    }
+
+    /// The MIDI note number, or `None` if it lies outside 0..=127.
+    pub fn midi(&self) -> Option<u8> {
+        let midi = (self.octave as i16 + 1) * 12
+            + self.name.letter.natural_pitch() as i16
+            + self.name.modifier.semitones() as i16;
+        u8::try_from(midi).ok().filter(|m| *m <= 127)
+    }
+}
+
+impl Display for Pitch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.name.letter)?;
+        if self.name.modifier != Accidental::Natural {
+            write!(f, "{}", self.name.modifier.symbol())?;
+        }
+        write!(f, "{}", self.octave)
+    }
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub struct Chord {
     name: ChordName,
     notes: ActivePitches,
 }
 
+impl Chord {
+    pub fn name(&self) -> ChordName {
+        self.name
+    }
+
+    pub fn notes(&self) -> ActivePitches {
+        self.notes
+    }
+
+    pub fn bass(&self) -> u8 {
+        self.notes.iter().next().unwrap()
+    }
+
+    pub fn bass_name(&self) -> NoteName {
+        self.spell(self.bass())
+    }
+
+    /// Each pitch spelled by its interval above the root, so a C diminished
+    /// seventh reads C, E♭, G♭, B𝄫 whatever spelling the root gets.
+    pub fn spelled_pitches(&self) -> Vec<Pitch> {
+        self.notes
+            .iter()
+            .map(|p| Pitch::spelled(p, self.spell(p)).unwrap_or(Pitch::from_midi(p)))
+            .collect()
+    }
+
+    fn spell(&self, pitch: u8) -> NoteName {
+        let root = self.name.root();
+        let interval = (pitch + 12 - root.pitch_class()) % 12;
+        root.spelled_up(interval, self.name.mode.letter_steps(interval))
+            .unwrap_or(NoteName::name_of(pitch))
+    }
+
+    pub fn inversion(&self) -> Inversion {
+        match self.bass_interval() {
+            0 => Inversion::Root,
+            3 | 4 => Inversion::First,
+            6..=8 => Inversion::Second,
+            interval if self.name.mode.is_seventh(interval) => Inversion::Third,
+            _ => Inversion::Other,
+        }
+    }
+
+    fn bass_interval(&self) -> u8 {
+        (self.bass() + 12 - self.name.root().pitch_class()) % 12
+    }
+}
+
 impl Display for Chord {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{} ({:?})",
-            self.name,
-            self.notes.iter().collect::<Vec<_>>()
-        )
+        write!(f, "{}", self.name)?;
+        if self.inversion() != Inversion::Root {
+            write!(f, "/{}", self.bass_name().to_string().trim_end())?;
+        }
+        if f.alternate() {
+            let pitches = self
+                .spelled_pitches()
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>();
+            write!(f, " ({})", pitches.join(", "))
+        } else {
+            write!(f, " ({:?})", self.notes.iter().collect::<Vec<_>>())
+        }
     }
 }
 
+/// Which chord member sounds in the bass. `Other` covers basses that are
+/// not the root, third, fifth or seventh, such as the ninth of a ninth chord
+/// or the fourth of a suspended chord.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Inversion {
+    Root,
+    First,
+    Second,
+    Third,
+    Other,
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub struct ChordName {
     note: NoteLetter,
@@ -170,11 +379,186 @@ impl Display for ChordName {
 
 impl ChordName {
     pub fn new(active: ActivePitches) -> Option<Self> {
-        SimpleChordInfo::new(active).map(|info| info.mode())
+        Self::best(active, &ChordContext::default())
+    }
+
+    pub fn best(active: ActivePitches, context: &ChordContext) -> Option<Self> {
+        Self::candidates(active, context).first().map(|c| c.name)
+    }
+
+    /// Every plausible reading of `active`, best first. Readings that
+    /// account for every pitch class come from the `ChordMode` templates;
+    /// triads that ignore some of the pitches are included as weaker
+    /// alternatives. Scores are normalized to sum to 1.
+    pub fn candidates(active: ActivePitches, context: &ChordContext) -> Vec<ChordCandidate> {
+        let reduced = ReducedPitches::new(active);
+        let mut readings = vec![];
+        for mode in all::<ChordMode>() {
+            for root in reduced.iter() {
+                if mode.matches(reduced.intervals_above(root)) {
+                    readings.push((root, mode, true));
+                }
+            }
+        }
+        for (root, mode) in stacked_triads(reduced) {
+            if !readings.iter().any(|(r, m, _)| *r == root && *m == mode) {
+                readings.push((root, mode, false));
+            }
+        }
+
+        let mut result = readings
+            .iter()
+            .map(|(root, mode, exact)| ChordCandidate {
+                name: Self::from_root(*root, *mode),
+                score: context.score(active, *root, *mode, *exact),
+            })
+            .collect::<Vec<_>>();
+        let total = result.iter().map(|c| c.score).sum::<f64>();
+        for candidate in result.iter_mut() {
+            candidate.score /= total;
+        }
+        result.sort_by(|c1, c2| c2.score.total_cmp(&c1.score));
+        result
+    }
+
+    /// A power chord leaves out the third, so its root is spelled as for the
+    /// minor triad it usually stands in for, rather than as a major root:
+    /// C♯ Power next to C♯ Minor, not D♭ Power.
+    fn from_root(root: u8, mode: ChordMode) -> Self {
+        let (note, accidental) = if mode.has_minor_third() || mode == ChordMode::Power {
+            MINOR_ROOT_IDS[root as usize]
+        } else {
+            MAJOR_ROOT_IDS[root as usize]
+        };
+        Self {
+            note,
+            accidental,
+            mode,
+        }
+    }
+
+    pub fn root(&self) -> NoteName {
+        NoteName {
+            letter: self.note,
+            modifier: self.accidental,
+        }
+    }
+
+    pub fn mode(&self) -> ChordMode {
+        self.mode
+    }
+
+    /// A lead-sheet symbol such as "Cmaj7"; it parses back to this name.
+    pub fn symbol(&self) -> String {
+        format!(
+            "{}{}",
+            self.root().to_string().trim_end(),
+            self.mode.symbol()
+        )
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ChordCandidate {
+    name: ChordName,
+    score: f64,
+}
+
+impl ChordCandidate {
+    pub fn name(&self) -> ChordName {
+        self.name
+    }
+
+    pub fn score(&self) -> f64 {
+        self.score
+    }
+}
+
+/// What is known about the surroundings of a pitch set beyond the pitches
+/// themselves: how hard each pitch was struck and the chord heard before it.
 #[derive(Copy, Clone, Debug)]
+pub struct ChordContext {
+    velocities: [u8; 128],
+    previous: Option<ChordName>,
+}
+
+impl Default for ChordContext {
+    fn default() -> Self {
+        Self {
+            velocities: [0; 128],
+            previous: None,
+        }
+    }
+}
+
+impl ChordContext {
+    pub fn new(previous: Option<ChordName>) -> Self {
+        Self {
+            previous,
+            ..Self::default()
+        }
+    }
+
+    pub fn update_from(&mut self, msg: &MidiMsg) {
+        if let Some((pitch, velocity)) = note_velocity_from(msg) {
+            self.velocities[pitch as usize] = velocity;
+        }
+    }
+
+    pub fn set_previous(&mut self, previous: Option<ChordName>) {
+        self.previous = previous;
+    }
+
+    fn weight(&self, pitch: u8) -> f64 {
+        match self.velocities[pitch as usize] {
+            0 => 1.0,
+            v => v as f64 / 127.0,
+        }
+    }
+
+    /// Exact template matches dominate; beyond that, a root in the bass
+    /// counts most, followed by the previous chord, the loudness of the
+    /// root, its doublings and finally the order of `ChordMode`.
+    fn score(&self, active: ActivePitches, root: u8, mode: ChordMode, exact: bool) -> f64 {
+        let fit = if exact {
+            2.0
+        } else {
+            3.0 / ReducedPitches::new(active).iter().count() as f64
+        };
+        let preference = 1.0
+            - all::<ChordMode>().position(|m| m == mode).unwrap() as f64
+                / cardinality::<ChordMode>() as f64;
+        let bass = if active.iter().next().is_some_and(|b| b % 12 == root) {
+            0.5
+        } else {
+            0.0
+        };
+        let root_pitches = active
+            .iter()
+            .filter(|p| *p % 12 == root)
+            .collect::<Vec<_>>();
+        let doublings = root_pitches.len().saturating_sub(1) as f64;
+        let total_weight = active.iter().map(|p| self.weight(p)).sum::<f64>();
+        let root_weight = root_pitches.iter().map(|p| self.weight(*p)).sum::<f64>();
+        let previous = self.previous.map_or(0.0, |prev| {
+            let motion = (root + 12 - prev.root().pitch_class()) % 12;
+            if motion == 0 && prev.mode == mode {
+                0.3
+            } else if motion == 5 || motion == 7 {
+                0.15
+            } else {
+                0.0
+            }
+        });
+        fit + bass
+            + previous
+            + 0.3 * root_weight / total_weight
+            + 0.1 * doublings
+            + 0.1 * preference
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Sequence)]
 pub enum ScaleMode {
     Major,
     Minor,
@@ -188,6 +572,15 @@ pub enum ScaleMode {
     MelodicMinor,
     Diminished,
     Augmented,
+    MajorPentatonic,
+    MinorPentatonic,
+    Blues,
+    BebopDominant,
+    BebopMajor,
+    HungarianMinor,
+    DoubleHarmonic,
+    Hirajoshi,
+    InSen,
 }
 
 impl ScaleMode {
@@ -202,18 +595,18 @@ impl ScaleMode {
             ScaleMode::Locrian => ScalePattern::mode_rotation(6),
             ScaleMode::HarmonicMinor => ScalePattern::standard([2, 1, 2, 2, 1, 3, 1]),
             ScaleMode::MelodicMinor => ScalePattern::standard([2, 1, 2, 2, 2, 2, 1]),
-            ScaleMode::WholeTone => ScalePattern {
-                num_jumps: 6,
-                jumps: [2, 2, 2, 2, 2, 2, 0, 0],
-            },
-            ScaleMode::Diminished => ScalePattern {
-                num_jumps: 8,
-                jumps: [2, 1, 2, 1, 2, 1, 2, 1],
-            },
-            ScaleMode::Augmented => ScalePattern {
-                num_jumps: 6,
-                jumps: [3, 1, 3, 1, 3, 1, 0, 0],
-            },
+            ScaleMode::WholeTone => ScalePattern::new(&[2, 2, 2, 2, 2, 2]),
+            ScaleMode::Diminished => ScalePattern::new(&[2, 1, 2, 1, 2, 1, 2, 1]),
+            ScaleMode::Augmented => ScalePattern::new(&[3, 1, 3, 1, 3, 1]),
+            ScaleMode::MajorPentatonic => ScalePattern::new(&[2, 2, 3, 2, 3]),
+            ScaleMode::MinorPentatonic => ScalePattern::new(&[3, 2, 2, 3, 2]),
+            ScaleMode::Blues => ScalePattern::new(&[3, 2, 1, 1, 3, 2]),
+            ScaleMode::BebopDominant => ScalePattern::new(&[2, 2, 1, 2, 2, 1, 1, 1]),
+            ScaleMode::BebopMajor => ScalePattern::new(&[2, 2, 1, 2, 1, 1, 2, 1]),
+            ScaleMode::HungarianMinor => ScalePattern::standard([2, 1, 3, 1, 1, 3, 1]),
+            ScaleMode::DoubleHarmonic => ScalePattern::standard([1, 3, 1, 2, 1, 3, 1]),
+            ScaleMode::Hirajoshi => ScalePattern::new(&[2, 1, 4, 1, 4]),
+            ScaleMode::InSen => ScalePattern::new(&[1, 4, 2, 3, 2]),
         }
     }
 
@@ -225,9 +618,114 @@ impl ScaleMode {
         .reversed()
     }
 
+    pub fn notes_going_up(&self, root: NoteName) -> impl Iterator<Item = u8> {
+        Scale::from(*self).notes_going_up(root)
+    }
+
+    pub fn notes_going_down(&self, root: NoteName) -> impl Iterator<Item = u8> {
+        Scale::from(*self).notes_going_down(root)
+    }
+
+    pub fn note_up(&self, root: NoteName, current: u8, interval: usize) -> Option<u8> {
+        Scale::from(*self).note_up(root, current, interval)
+    }
+
+    pub fn note_down(&self, root: NoteName, current: u8, interval: usize) -> Option<u8> {
+        Scale::from(*self).note_down(root, current, interval)
+    }
+
+    pub fn num_notes(&self) -> usize {
+        self.pattern_up().num_jumps
+    }
+
+    /// The church modes along with harmonic and melodic minor: the scales
+    /// that key finding and Roman numeral analysis consider.
+    pub fn is_tonal(&self) -> bool {
+        self.num_notes() == 7
+            && !matches!(self, ScaleMode::HungarianMinor | ScaleMode::DoubleHarmonic)
+    }
+
+    /// The pitch classes of one octave of the scale, in ascending order from
+    /// the root.
+    pub fn pitch_classes(&self, root: NoteName) -> Vec<u8> {
+        Scale::from(*self).pitch_classes(root)
+    }
+}
+
+/// A scale given by its semitone steps rather than a `ScaleMode`, such as
+/// one loaded from a configuration file. Descending it uses the ascending
+/// steps in reverse unless `with_descending` says otherwise.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Scale {
+    up: ScalePattern,
+    down: ScalePattern,
+}
+
+impl Scale {
+    /// `None` unless the steps are positive and add up to an octave.
+    pub fn from_steps(steps: &[u8]) -> Option<Self> {
+        let pattern = ScalePattern::from_steps(steps)?;
+        Some(Self {
+            up: pattern,
+            down: pattern.reversed(),
+        })
+    }
+
+    /// Bit `i` of `mask` is set when the scale includes the pitch `i`
+    /// semitones above the root, as in `ReducedPitches::intervals_above`.
+    /// `None` unless the root itself is included.
+    pub fn from_mask(mask: u16) -> Option<Self> {
+        if mask & 1 == 0 {
+            return None;
+        }
+        let intervals = (0..12)
+            .filter(|i| mask & (1 << i) != 0)
+            .collect::<Vec<u8>>();
+        let steps = (0..intervals.len())
+            .map(|i| intervals.get(i + 1).copied().unwrap_or(12) - intervals[i])
+            .collect::<Vec<_>>();
+        Self::from_steps(&steps)
+    }
+
+    /// Replaces the descending form, given as steps upward from the root
+    /// like the ascending ones.
+    pub fn with_descending(&self, steps: &[u8]) -> Option<Self> {
+        Some(Self {
+            up: self.up,
+            down: ScalePattern::from_steps(steps)?.reversed(),
+        })
+    }
+
+    pub fn steps(&self) -> Vec<u8> {
+        self.up.jumps[..self.up.num_jumps].to_vec()
+    }
+
+    pub fn mask(&self) -> u16 {
+        let mut interval = 0;
+        let mut result = 0;
+        for step in self.steps() {
+            result |= 1 << interval;
+            interval += step;
+        }
+        result
+    }
+
+    pub fn num_notes(&self) -> usize {
+        self.up.num_jumps
+    }
+
+    /// The pitch classes of one octave of the scale, in ascending order from
+    /// the root.
+    pub fn pitch_classes(&self, root: NoteName) -> Vec<u8> {
+        self.notes_going_up(root)
+            .take(self.num_notes())
+            .map(|p| p % 12)
+            .collect()
+    }
+
     pub fn notes_going_up(&self, root: NoteName) -> impl Iterator<Item = u8> {
         ScaleUpIterator {
-            pattern: self.pattern_up(),
+            pattern: self.up,
             current: root.lowest_midi_note(),
             count: 0,
         }
@@ -236,7 +734,7 @@ impl ScaleMode {
     pub fn notes_going_down(&self, root: NoteName) -> impl Iterator<Item = u8> {
         let root_note = root.lowest_midi_note();
         ScaleDownIterator {
-            pattern: self.pattern_down(),
+            pattern: self.down,
             current: root_note + if root_note > 7 { 108 } else { 120 },
             count: 0,
         }
@@ -245,18 +743,295 @@ impl ScaleMode {
     pub fn note_up(&self, root: NoteName, current: u8, interval: usize) -> Option<u8> {
         self.notes_going_up(root)
             .skip_while(|n| *n < current)
-            .skip(interval - 1)
+            .skip(interval.saturating_sub(1))
             .next()
     }
 
     pub fn note_down(&self, root: NoteName, current: u8, interval: usize) -> Option<u8> {
         self.notes_going_down(root)
             .skip_while(|n| *n > current)
-            .skip(interval - 1)
+            .skip(interval.saturating_sub(1))
             .next()
     }
 }
 
+/// Writes the steps, followed by the descending steps after a slash when
+/// they differ, as in "2 1 2 2 2 2 1 / 2 1 2 2 1 2 2".
+impl Display for Scale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |steps: &[u8]| {
+            steps
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        write!(f, "{}", join(&self.steps()))?;
+        if self.down != self.up.reversed() {
+            let down = self.down.reversed();
+            write!(f, " / {}", join(&down.jumps[..down.num_jumps]))?;
+        }
+        Ok(())
+    }
+}
+
+impl From<ScaleMode> for Scale {
+    fn from(mode: ScaleMode) -> Self {
+        Self {
+            up: mode.pattern_up(),
+            down: mode.pattern_down(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Key {
+    tonic: NoteName,
+    mode: ScaleMode,
+}
+
+impl Key {
+    pub fn new(tonic: NoteName, mode: ScaleMode) -> Self {
+        Self { tonic, mode }
+    }
+
+    /// Spells the tonic from sharps or flats depending on whether the scale
+    /// has a minor third, as `ChordName` does for chord roots.
+    pub fn from_pitch_class(tonic: u8, mode: ScaleMode) -> Self {
+        let minor = mode.pitch_classes(NoteName::name_of(0)).contains(&3);
+        let (letter, modifier) = if minor {
+            MINOR_ROOT_IDS[(tonic % 12) as usize]
+        } else {
+            MAJOR_ROOT_IDS[(tonic % 12) as usize]
+        };
+        Self::new(NoteName { letter, modifier }, mode)
+    }
+
+    pub fn tonic(&self) -> NoteName {
+        self.tonic
+    }
+
+    pub fn mode(&self) -> ScaleMode {
+        self.mode
+    }
+
+    pub fn pitch_classes(&self) -> Vec<u8> {
+        self.mode.pitch_classes(self.tonic)
+    }
+
+    /// This key if its scale has seven notes, otherwise the major or minor
+    /// key on the same tonic that shares its third.
+    pub fn heptatonic(&self) -> Self {
+        if self.mode.num_notes() == 7 {
+            *self
+        } else if self
+            .pitch_classes()
+            .contains(&((self.tonic.pitch_class() + 3) % 12))
+        {
+            Self::new(self.tonic, ScaleMode::Minor)
+        } else {
+            Self::new(self.tonic, ScaleMode::Major)
+        }
+    }
+
+    /// The scale with one note on each letter, starting from the tonic.
+    /// Theoretical keys such as G♯ major use double sharps or flats.
+    pub fn spelled_scale(&self) -> Vec<NoteName> {
+        let key = self.heptatonic();
+        let mut letter = key.tonic.letter;
+        let mut result = vec![];
+        for pitch in key.pitch_classes() {
+            result.push(NoteName::with_letter(letter, pitch).unwrap_or(NoteName::name_of(pitch)));
+            letter = letter.next();
+        }
+        result
+    }
+
+    /// Sharps in the key signature, negative for flats.
+    pub fn signature(&self) -> i8 {
+        self.spelled_scale()
+            .iter()
+            .map(|n| n.modifier.semitones())
+            .sum()
+    }
+
+    /// Spells `pitch` as it would be written in this key. Scale tones keep
+    /// their letters; chromatic tones are spelled as they appear in the
+    /// nearest related mode on the same tonic, so C major gives E♭, A♭ and
+    /// B♭ from the parallel minor, F♯ from Lydian and D♭ from Phrygian.
+    pub fn spell(&self, pitch: u8) -> NoteName {
+        let pc = pitch % 12;
+        let key = self.heptatonic();
+        std::iter::once(key.mode)
+            .chain(SPELLING_MODES)
+            .flat_map(|mode| Key::new(key.tonic, mode).spelled_scale())
+            .find(|n| n.pitch_class() == pc)
+            .unwrap_or(NoteName::name_of(pitch))
+    }
+
+    pub fn spell_pitch(&self, pitch: u8) -> Pitch {
+        Pitch::spelled(pitch, self.spell(pitch)).unwrap_or(Pitch::from_midi(pitch))
+    }
+
+    /// Where `pitch` lies in the scale. Pitches outside a seven-note scale
+    /// take the degree of their letter as spelled in this key, so in C major
+    /// E♭ is ♭3 and F♯ is ♯4; in other scales they take the nearest degree,
+    /// raising the one below on a tie.
+    pub fn degree_of(&self, pitch: u8) -> ScaleDegree {
+        let scale = self.pitch_classes();
+        let shift_from = |degree: usize| {
+            let shift = ((pitch % 12 + 12 - scale[degree]) % 12) as i8;
+            if shift > 6 {
+                shift - 12
+            } else {
+                shift
+            }
+        };
+        if self.mode.num_notes() == 7 {
+            let letter = self.spell(pitch).letter;
+            let degree = (letter.index() + 7 - self.tonic.letter.index()) % 7;
+            if let Some(alteration) = Accidental::from_semitones(shift_from(degree)) {
+                return ScaleDegree { degree, alteration };
+            }
+        }
+        let degree = (0..scale.len())
+            .min_by_key(|d| (shift_from(*d).abs(), shift_from(*d) < 0))
+            .unwrap();
+        ScaleDegree {
+            degree,
+            alteration: Accidental::from_semitones(shift_from(degree)).unwrap(),
+        }
+    }
+
+    /// Moves `pitch` by `steps` scale degrees, downward if negative, keeping
+    /// any chromatic alteration: in C major, E moves up two steps to G and
+    /// E♭ to G♭. `None` if the result is outside the MIDI range.
+    pub fn transpose_diatonic(&self, pitch: u8, steps: i32) -> Option<u8> {
+        let (position, alteration) = self.scale_position(pitch);
+        self.pitch_at(
+            position + steps,
+            alteration,
+            self.tonic.pitch_class() as i32,
+        )
+    }
+
+    /// Moves `pitch` to the same degree, alteration and octave of `other`,
+    /// whose tonic is taken to be the one nearest this key's tonic. Turning
+    /// C major into C Dorian lowers every E and B; turning it into D major
+    /// moves everything up a whole step. `None` if the result is outside the
+    /// MIDI range, or if the two scales differ in size, since their degrees
+    /// then don't correspond.
+    pub fn transpose_to(&self, pitch: u8, other: Key) -> Option<u8> {
+        if self.mode.num_notes() != other.mode.num_notes() {
+            return None;
+        }
+        let (position, alteration) = self.scale_position(pitch);
+        let tonic = self.tonic.pitch_class() as i32;
+        let shift = (other.tonic.pitch_class() as i32 - tonic + 6).rem_euclid(12) - 6;
+        other.pitch_at(position, alteration, tonic + shift)
+    }
+
+    /// Semitones above the tonic of each degree of the scale.
+    fn degree_offsets(&self) -> Vec<i32> {
+        self.pitch_classes()
+            .iter()
+            .map(|pc| ((pc + 12 - self.tonic.pitch_class()) % 12) as i32)
+            .collect()
+    }
+
+    /// Scale steps from the tonic below MIDI 0 to the degree of `pitch`,
+    /// along with its alteration in semitones.
+    fn scale_position(&self, pitch: u8) -> (i32, i32) {
+        let offsets = self.degree_offsets();
+        let degree = self.degree_of(pitch);
+        let alteration = degree.alteration.semitones() as i32;
+        let tonic = self.tonic.pitch_class() as i32;
+        let octave = (pitch as i32 - alteration - tonic - offsets[degree.degree]).div_euclid(12);
+        (
+            octave * offsets.len() as i32 + degree.degree as i32,
+            alteration,
+        )
+    }
+
+    fn pitch_at(&self, position: i32, alteration: i32, tonic: i32) -> Option<u8> {
+        let offsets = self.degree_offsets();
+        let num_notes = offsets.len() as i32;
+        let result = tonic
+            + 12 * position.div_euclid(num_notes)
+            + offsets[position.rem_euclid(num_notes) as usize]
+            + alteration;
+        u8::try_from(result).ok().filter(|p| *p <= 127)
+    }
+
+    /// `count` pitches stacked in thirds of this scale from `root`, stopping
+    /// early at the top of the MIDI range.
+    pub fn stacked_thirds(&self, root: u8, count: usize) -> Vec<u8> {
+        (0..count)
+            .map_while(|i| self.transpose_diatonic(root, 2 * i as i32))
+            .collect()
+    }
+
+    /// The interval between two pitches, named by their spellings in this key.
+    pub fn interval_between(&self, a: u8, b: u8) -> Option<Interval> {
+        Interval::between(self.spell_pitch(a), self.spell_pitch(b))
+    }
+
+    /// `name` with its root spelled as it would be in this key.
+    pub fn spell_chord(&self, name: ChordName) -> ChordName {
+        let root = self.spell(name.root().pitch_class());
+        ChordName {
+            note: root.letter,
+            accidental: root.modifier,
+            mode: name.mode,
+        }
+    }
+}
+
+/// A position in a scale, counted from 0 at the root, with any chromatic
+/// alteration. Displayed counting from 1, as in "♭3".
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ScaleDegree {
+    degree: usize,
+    alteration: Accidental,
+}
+
+impl ScaleDegree {
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    pub fn alteration(&self) -> Accidental {
+        self.alteration
+    }
+}
+
+impl Display for ScaleDegree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.alteration != Accidental::Natural {
+            write!(f, "{}", self.alteration.symbol())?;
+        }
+        write!(f, "{}", self.degree + 1)
+    }
+}
+
+/// Modes consulted, in order, for the spelling of chromatic tones.
+const SPELLING_MODES: [ScaleMode; 8] = [
+    ScaleMode::Major,
+    ScaleMode::Minor,
+    ScaleMode::MelodicMinor,
+    ScaleMode::HarmonicMinor,
+    ScaleMode::Lydian,
+    ScaleMode::Phrygian,
+    ScaleMode::Mixolydian,
+    ScaleMode::Dorian,
+];
+
+impl Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {:?}", self.tonic, self.mode)
+    }
+}
+
 struct ScaleUpIterator {
     pattern: ScalePattern,
     current: u8,
@@ -299,10 +1074,10 @@ impl Iterator for ScaleDownIterator {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
 struct ScalePattern {
     num_jumps: usize,
-    jumps: [u8; 8],
+    jumps: [u8; 12],
 }
 
 impl ScalePattern {
@@ -318,17 +1093,30 @@ impl ScalePattern {
         result
     }
 
-    fn standard(intervals: [u8; 7]) -> Self {
-        let mut jumps = [0; 8];
-        for (i, j) in intervals.iter().enumerate() {
-            jumps[i] = *j;
-        }
+    fn new(intervals: &[u8]) -> Self {
+        let mut jumps = [0; 12];
+        jumps[..intervals.len()].copy_from_slice(intervals);
         Self {
-            num_jumps: 7,
+            num_jumps: intervals.len(),
             jumps,
         }
     }
 
+    fn from_steps(steps: &[u8]) -> Option<Self> {
+        if steps.is_empty()
+            || steps.contains(&0)
+            || steps.iter().map(|s| *s as usize).sum::<usize>() != 12
+        {
+            None
+        } else {
+            Some(Self::new(steps))
+        }
+    }
+
+    fn standard(intervals: [u8; 7]) -> Self {
+        Self::new(&intervals)
+    }
+
     fn mode_rotation(rotation: usize) -> Self {
         let major = [2, 2, 1, 2, 2, 2, 1];
         let mut destination = [0; 7];
@@ -339,83 +1127,275 @@ impl ScalePattern {
     }
 }
 
-struct SimpleChordInfo {
-    pitches: Vec<u8>,
-    root_index: usize,
-    thirds: Vec<u8>,
-}
-
-impl SimpleChordInfo {
-    fn new(active: ActivePitches) -> Option<Self> {
-        let (pitches, diffs) = ReducedPitches::new(active).pitches_diffs();
-        first_third_index(&diffs).map(|root_index| {
-            let thirds = (0..diffs.len())
-                .map(|i| diffs[(root_index + i) % diffs.len()])
-                .collect();
-            Self {
-                pitches,
-                root_index,
-                thirds,
-            }
-        })
-    }
-
-    fn root_pitch_index(&self) -> usize {
-        self.pitches[self.root_index] as usize
-    }
-
-    fn mode(&self) -> ChordName {
-        let first = self.thirds[0];
-        let second = self.thirds[1];
-        if first == 3 {
-            let (note, accidental) = MINOR_ROOT_IDS[self.root_pitch_index()];
-            ChordName {
-                note,
-                accidental,
-                mode: if second == 3 {
-                    ChordMode::Diminished
-                } else {
-                    ChordMode::Minor
-                },
-            }
-        } else {
-            let (note, accidental) = MAJOR_ROOT_IDS[self.root_pitch_index()];
-            ChordName {
-                note,
-                accidental,
-                mode: if second == 3 {
-                    ChordMode::Major
-                } else {
-                    ChordMode::Augmented
-                },
-            }
-        }
-    }
-}
-
+/// Chord qualities, listed in the order of preference used when a pitch set
+/// matches more than one of them. Tertian chords come first, followed by the
+/// suspended, added-tone and power chords that lack a stack of thirds.
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Sequence)]
 pub enum ChordMode {
     Major,
     Minor,
     Diminished,
     Augmented,
+    Dominant7,
+    Major7,
+    Minor7,
+    HalfDiminished7,
+    Diminished7,
+    MinorMajor7,
+    Major6,
+    Minor6,
+    Dominant9,
+    Major9,
+    Minor9,
+    Dominant11,
+    Dominant13,
+    Dominant7Sus4,
+    Sus4,
+    Sus2,
+    Add9,
+    MinorAdd9,
+    Power,
 }
 
 impl ChordMode {
     pub fn scales(&self) -> Vec<ScaleMode> {
         match self {
-            ChordMode::Major => vec![ScaleMode::Major, ScaleMode::Lydian, ScaleMode::Mixolydian],
+            ChordMode::Major => vec![
+                ScaleMode::Major,
+                ScaleMode::Lydian,
+                ScaleMode::Mixolydian,
+                ScaleMode::MajorPentatonic,
+                ScaleMode::BebopMajor,
+                ScaleMode::DoubleHarmonic,
+            ],
+            ChordMode::Major6 => vec![
+                ScaleMode::Major,
+                ScaleMode::Lydian,
+                ScaleMode::Mixolydian,
+                ScaleMode::MajorPentatonic,
+                ScaleMode::BebopMajor,
+            ],
             ChordMode::Minor => vec![
                 ScaleMode::Minor,
                 ScaleMode::MelodicMinor,
                 ScaleMode::HarmonicMinor,
                 ScaleMode::Dorian,
                 ScaleMode::Phrygian,
+                ScaleMode::MinorPentatonic,
+                ScaleMode::Blues,
+                ScaleMode::HungarianMinor,
+                ScaleMode::Hirajoshi,
             ],
             ChordMode::Diminished => vec![ScaleMode::Diminished],
             ChordMode::Augmented => vec![ScaleMode::Augmented, ScaleMode::WholeTone],
+            ChordMode::Dominant7 => vec![
+                ScaleMode::Mixolydian,
+                ScaleMode::BebopDominant,
+                ScaleMode::Blues,
+            ],
+            ChordMode::Dominant9 | ChordMode::Dominant11 | ChordMode::Dominant13 => {
+                vec![ScaleMode::Mixolydian, ScaleMode::BebopDominant]
+            }
+            ChordMode::Major7 | ChordMode::Major9 => vec![
+                ScaleMode::Major,
+                ScaleMode::Lydian,
+                ScaleMode::MajorPentatonic,
+                ScaleMode::BebopMajor,
+            ],
+            ChordMode::Minor7 | ChordMode::Minor9 => vec![
+                ScaleMode::Dorian,
+                ScaleMode::Minor,
+                ScaleMode::Phrygian,
+                ScaleMode::MinorPentatonic,
+                ScaleMode::Blues,
+            ],
+            ChordMode::Minor6 => vec![ScaleMode::Dorian, ScaleMode::MelodicMinor],
+            ChordMode::HalfDiminished7 => vec![ScaleMode::Locrian],
+            ChordMode::Diminished7 => vec![ScaleMode::Diminished, ScaleMode::HarmonicMinor],
+            ChordMode::MinorMajor7 => vec![
+                ScaleMode::MelodicMinor,
+                ScaleMode::HarmonicMinor,
+                ScaleMode::HungarianMinor,
+            ],
+            ChordMode::Dominant7Sus4 => {
+                vec![ScaleMode::Mixolydian, ScaleMode::Dorian, ScaleMode::InSen]
+            }
+            ChordMode::Sus4 => vec![ScaleMode::Mixolydian, ScaleMode::Major, ScaleMode::Dorian],
+            ChordMode::Sus2 => vec![
+                ScaleMode::Major,
+                ScaleMode::Mixolydian,
+                ScaleMode::Dorian,
+                ScaleMode::Minor,
+                ScaleMode::MajorPentatonic,
+                ScaleMode::Hirajoshi,
+            ],
+            ChordMode::Add9 => vec![
+                ScaleMode::Major,
+                ScaleMode::Lydian,
+                ScaleMode::Mixolydian,
+                ScaleMode::MajorPentatonic,
+            ],
+            ChordMode::MinorAdd9 => {
+                vec![ScaleMode::Minor, ScaleMode::Dorian, ScaleMode::MelodicMinor]
+            }
+            ChordMode::Power => vec![
+                ScaleMode::Major,
+                ScaleMode::Minor,
+                ScaleMode::Mixolydian,
+                ScaleMode::Dorian,
+                ScaleMode::MajorPentatonic,
+                ScaleMode::MinorPentatonic,
+                ScaleMode::Blues,
+            ],
         }
     }
+
+    pub fn has_minor_third(&self) -> bool {
+        self.intervals().0 & (1 << 3) != 0
+    }
+
+    /// Semitones above the root that must be present, and those that may be
+    /// present but are often left out of a voicing.
+    fn intervals(&self) -> (u16, u16) {
+        let (required, optional): (&[u8], &[u8]) = match self {
+            ChordMode::Major => (&[0, 4, 7], &[]),
+            ChordMode::Minor => (&[0, 3, 7], &[]),
+            ChordMode::Diminished => (&[0, 3, 6], &[]),
+            ChordMode::Augmented => (&[0, 4, 8], &[]),
+            ChordMode::Dominant7 => (&[0, 4, 10], &[7]),
+            ChordMode::Major7 => (&[0, 4, 11], &[7]),
+            ChordMode::Minor7 => (&[0, 3, 10], &[7]),
+            ChordMode::HalfDiminished7 => (&[0, 3, 6, 10], &[]),
+            ChordMode::Diminished7 => (&[0, 3, 6, 9], &[]),
+            ChordMode::MinorMajor7 => (&[0, 3, 11], &[7]),
+            ChordMode::Major6 => (&[0, 4, 7, 9], &[]),
+            ChordMode::Minor6 => (&[0, 3, 7, 9], &[]),
+            ChordMode::Dominant9 => (&[0, 2, 4, 10], &[7]),
+            ChordMode::Major9 => (&[0, 2, 4, 11], &[7]),
+            ChordMode::Minor9 => (&[0, 2, 3, 10], &[7]),
+            ChordMode::Dominant11 => (&[0, 2, 5, 10], &[4, 7]),
+            ChordMode::Dominant13 => (&[0, 4, 9, 10], &[2, 5, 7]),
+            ChordMode::Dominant7Sus4 => (&[0, 5, 7, 10], &[]),
+            ChordMode::Sus4 => (&[0, 5, 7], &[]),
+            ChordMode::Sus2 => (&[0, 2, 7], &[]),
+            ChordMode::Add9 => (&[0, 2, 4, 7], &[]),
+            ChordMode::MinorAdd9 => (&[0, 2, 3, 7], &[]),
+            ChordMode::Power => (&[0, 7], &[]),
+        };
+        (interval_mask(required), interval_mask(optional))
+    }
+
+    /// How many letter names above the root a chord member `interval`
+    /// semitones above it should be spelled.
+    fn letter_steps(&self, interval: u8) -> usize {
+        match interval {
+            0 => 0,
+            1 | 2 => 1,
+            3 | 4 => 2,
+            5 => 3,
+            6..=8 => 4,
+            9 if *self == ChordMode::Diminished7 => 6,
+            9 => 5,
+            _ => 6,
+        }
+    }
+
+    /// Whether the chord member `interval` semitones above the root is the
+    /// seventh, rather than, say, the sixth of an added-sixth chord.
+    fn is_seventh(&self, interval: u8) -> bool {
+        let (required, optional) = self.intervals();
+        (required | optional) & (1 << interval) != 0 && self.letter_steps(interval) == 6
+    }
+
+    /// The pitch classes this quality requires when built on `root`.
+    pub fn pitch_classes(&self, root: u8) -> Vec<u8> {
+        let required = self.intervals().0;
+        (0..12)
+            .filter(|i| required & (1 << i) != 0)
+            .map(|i| (root + i) % 12)
+            .collect()
+    }
+
+    /// The suffix used after a Roman numeral; the numeral's case already
+    /// shows whether the third is major or minor.
+    pub fn figure(&self) -> &'static str {
+        match self {
+            ChordMode::Major | ChordMode::Minor => "",
+            ChordMode::Diminished => "°",
+            ChordMode::Augmented => "+",
+            ChordMode::Dominant7 | ChordMode::Minor7 => "7",
+            ChordMode::Major7 | ChordMode::MinorMajor7 => "maj7",
+            ChordMode::HalfDiminished7 => "ø7",
+            ChordMode::Diminished7 => "°7",
+            ChordMode::Major6 | ChordMode::Minor6 => "add6",
+            ChordMode::Dominant9 | ChordMode::Minor9 => "9",
+            ChordMode::Major9 => "maj9",
+            ChordMode::Dominant11 => "11",
+            ChordMode::Dominant13 => "13",
+            ChordMode::Dominant7Sus4 => "7sus4",
+            ChordMode::Sus4 => "sus4",
+            ChordMode::Sus2 => "sus2",
+            ChordMode::Add9 | ChordMode::MinorAdd9 => "add9",
+            ChordMode::Power => "5",
+        }
+    }
+
+    /// The suffix used after the root in a chord symbol such as "F♯m7♭5".
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            ChordMode::Major => "",
+            ChordMode::Minor => "m",
+            ChordMode::Diminished => "dim",
+            ChordMode::Augmented => "aug",
+            ChordMode::Dominant7 => "7",
+            ChordMode::Major7 => "maj7",
+            ChordMode::Minor7 => "m7",
+            ChordMode::HalfDiminished7 => "m7\u{266d}5",
+            ChordMode::Diminished7 => "dim7",
+            ChordMode::MinorMajor7 => "m(maj7)",
+            ChordMode::Major6 => "6",
+            ChordMode::Minor6 => "m6",
+            ChordMode::Dominant9 => "9",
+            ChordMode::Major9 => "maj9",
+            ChordMode::Minor9 => "m9",
+            ChordMode::Dominant11 => "11",
+            ChordMode::Dominant13 => "13",
+            ChordMode::Dominant7Sus4 => "7sus4",
+            ChordMode::Sus4 => "sus4",
+            ChordMode::Sus2 => "sus2",
+            ChordMode::Add9 => "add9",
+            ChordMode::MinorAdd9 => "madd9",
+            ChordMode::Power => "5",
+        }
+    }
+
+    fn matches(&self, intervals: u16) -> bool {
+        let (required, optional) = self.intervals();
+        intervals & required == required && intervals & !(required | optional) == 0
+    }
+}
+
+/// Pearson correlation of `xs` and `ys`, or 0 if either is constant.
+pub(crate) fn correlation(xs: &[f64], ys: &[f64]) -> f64 {
+    let mean_x = xs.iter().sum::<f64>() / xs.len() as f64;
+    let mean_y = ys.iter().sum::<f64>() / ys.len() as f64;
+    let mut covariance = 0.0;
+    let mut variance_x = 0.0;
+    let mut variance_y = 0.0;
+    for (x, y) in xs.iter().zip(ys.iter()) {
+        covariance += (x - mean_x) * (y - mean_y);
+        variance_x += (x - mean_x).powi(2);
+        variance_y += (y - mean_y).powi(2);
+    }
+    if variance_x == 0.0 || variance_y == 0.0 {
+        0.0
+    } else {
+        covariance / (variance_x * variance_y).sqrt()
+    }
+}
+
+fn interval_mask(intervals: &[u8]) -> u16 {
+    intervals.iter().fold(0, |mask, i| mask | (1 << i))
 }
 
 #[derive(Copy, Clone, Default, Eq, PartialEq, Debug)]
@@ -468,6 +1448,23 @@ impl PitchSequence {
         self.seq.push((time, msg.clone(), *current));
     }
 
+    /// A sequence sounding `notes`, which may overlap and come in any order.
+    /// Where one note ends as another of the same pitch and channel begins,
+    /// the note-off comes first.
+    pub fn from_notes(notes: &[Note]) -> Self {
+        let mut events = notes.iter().flat_map(|n| n.messages()).collect::<Vec<_>>();
+        events.sort_by(|(t1, m1), (t2, m2)| {
+            let is_on = |msg: &MidiMsg| note_velocity_from(msg).is_some_and(|(_, v)| v > 0);
+            t1.total_cmp(t2).then(is_on(m1).cmp(&is_on(m2)))
+        });
+        let mut result = Self::default();
+        let mut current = ActivePitches::default();
+        for (time, msg) in events.iter() {
+            result.push(*time, msg, &mut current);
+        }
+        result
+    }
+
     pub fn recording(&self) -> Recording {
         let mut result = Recording::default();
         for (time, msg, _) in self.seq.iter() {
@@ -516,15 +1513,160 @@ impl PitchSequence {
                 }
             }
         }
-        None
+        None
+    }
+
+    pub fn end_time(&self) -> f64 {
+        self.seq.last().map_or(0.0, |(t, _, _)| *t)
+    }
+
+    /// Total time each pitch class sounds, summed over every pitch that is
+    /// active at once.
+    pub fn pitch_class_durations(&self) -> [f64; 12] {
+        self.pitch_class_durations_between(f64::NEG_INFINITY, f64::INFINITY)
+    }
+
+    /// As `pitch_class_durations`, counting only the time between `start`
+    /// and `end`.
+    pub fn pitch_class_durations_between(&self, start: f64, end: f64) -> [f64; 12] {
+        let mut result = [0.0; 12];
+        for window in self.seq.windows(2) {
+            let (t, _, active) = &window[0];
+            let duration = window[1].0.min(end) - t.max(start);
+            if duration > 0.0 {
+                for pitch in active.iter() {
+                    result[(pitch % 12) as usize] += duration;
+                }
+            }
+        }
+        result
+    }
+
+    /// Moves every note by `semitones`.
+    pub fn transposed(&self, semitones: i16) -> Result<Self, OutOfRange> {
+        self.with_notes_moved(|note| u8::try_from(note as i16 + semitones).ok())
+    }
+
+    /// Moves every note by `steps` degrees of `key`'s scale.
+    pub fn transposed_diatonic(&self, key: Key, steps: i32) -> Result<Self, OutOfRange> {
+        self.with_notes_moved(|note| key.transpose_diatonic(note, steps))
+    }
+
+    /// Moves every note from its degree in `from` to the same degree in
+    /// `to`, as `Key::transpose_to` does, so it fails for every note when
+    /// the two scales differ in size.
+    pub fn transposed_to(&self, from: Key, to: Key) -> Result<Self, OutOfRange> {
+        self.with_notes_moved(|note| from.transpose_to(note, to))
+    }
+
+    fn with_notes_moved<F: Fn(u8) -> Option<u8>>(&self, mover: F) -> Result<Self, OutOfRange> {
+        let mut result = Self::default();
+        let mut current = ActivePitches::default();
+        let mut out_of_range = vec![];
+        for (t, msg, _) in self.seq.iter() {
+            match note_of(msg) {
+                Some(note) => match mover(note).filter(|n| *n <= 127) {
+                    Some(moved) => result.push(*t, &with_note(msg, moved), &mut current),
+                    None => out_of_range.push((*t, note)),
+                },
+                None => result.push(*t, msg, &mut current),
+            }
+        }
+        if out_of_range.is_empty() {
+            Ok(result)
+        } else {
+            Err(OutOfRange {
+                notes: out_of_range,
+            })
+        }
+    }
+
+    pub fn keys(&self) -> Vec<(Key, f64)> {
+        key_finder::ranked_keys(&self.pitch_class_durations())
+    }
+
+    pub fn beat_grid(&self) -> Option<BeatGrid> {
+        beat_tracker::beat_grid(self)
+    }
+
+    pub fn meter(&self) -> Option<Meter> {
+        meter_finder::meter(self)
+    }
+
+    /// The melody and the accompaniment, as separated by
+    /// `voice_separator::melody_and_accompaniment`.
+    pub fn melody_and_accompaniment(&self) -> (Self, Self) {
+        voice_separator::melody_and_accompaniment(self)
+    }
+
+    /// This sequence with its notes snapped to `grid` within the beats of
+    /// `beats`, and how far they had to move.
+    pub fn quantized(&self, beats: &BeatGrid, grid: &Grid) -> (Self, QuantizationError) {
+        quantizer::quantized(self, beats, grid)
+    }
+
+    /// This sequence with every time measured in beats of `grid`, so that
+    /// durations, chord timings and anything else derived from it come out
+    /// in beats rather than seconds.
+    pub fn in_beats(&self, grid: &BeatGrid) -> Self {
+        Self {
+            seq: self
+                .seq
+                .iter()
+                .map(|(time, msg, pitches)| (grid.beat_at(*time), msg.clone(), *pitches))
+                .collect(),
+        }
+    }
+
+    /// Every note with its true start and end, in order of onset, however
+    /// many sound at once. Each note-off ends the earliest note still
+    /// sounding on its pitch and channel, so a pitch struck again before it
+    /// is released yields overlapping notes. Notes still sounding at the end
+    /// last until `end_time`; note-offs with nothing to end are ignored.
+    pub fn notes(&self) -> Vec<Note> {
+        let mut result: Vec<Note> = vec![];
+        let mut sounding: HashMap<(u8, u8), VecDeque<usize>> = HashMap::new();
+        for (time, msg, _) in self.seq.iter() {
+            if let (Some((pitch, velocity)), Some(channel)) =
+                (note_velocity_from(msg), channel_of(msg))
+            {
+                let started = sounding.entry((channel as u8, pitch)).or_default();
+                if velocity > 0 {
+                    started.push_back(result.len());
+                    result.push(Note::new(*time, 0.0, pitch, velocity, channel));
+                } else if let Some(i) = started.pop_front() {
+                    result[i].duration = time - result[i].start;
+                }
+            }
+        }
+        let end = self.end_time();
+        for i in sounding.values().flatten() {
+            result[*i].duration = end - result[*i].start;
+        }
+        result
+    }
+
+    /// As `chords_starts_durations`, with each chord's timing named.
+    pub fn chord_spans(&self) -> Vec<ChordSpan> {
+        self.chords_starts_durations()
+            .into_iter()
+            .map(|(chord, start, duration)| ChordSpan {
+                chord,
+                start,
+                duration,
+            })
+            .collect()
     }
 
     pub fn chords_starts_durations(&self) -> Vec<(Chord, f64, f64)> {
         let mut pending = None;
         let mut result = vec![];
         let mut last_time = 0.0;
-        for (t, _, p) in self.seq.iter() {
-            if let Some(name) = ChordName::new(*p) {
+        let mut context = ChordContext::default();
+        for (t, msg, p) in self.seq.iter() {
+            context.update_from(msg);
+            if let Some(name) = ChordName::best(*p, &context) {
+                context.set_previous(Some(name));
                 if let Some((chord, time)) = pending {
                     result.push((chord, time, *t - time));
                     last_time = time;
@@ -539,6 +1681,223 @@ impl PitchSequence {
     }
 }
 
+/// A note-on and the note-off that ends it: `pitch` struck on `channel` at
+/// `start` seconds with `velocity`, sounding for `duration` seconds.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Note {
+    start: f64,
+    duration: f64,
+    pitch: u8,
+    velocity: u8,
+    channel: Channel,
+}
+
+impl Note {
+    pub fn new(start: f64, duration: f64, pitch: u8, velocity: u8, channel: Channel) -> Self {
+        Self {
+            start,
+            duration,
+            pitch,
+            velocity,
+            channel,
+        }
+    }
+
+    pub fn start(&self) -> f64 {
+        self.start
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.duration
+    }
+
+    pub fn end(&self) -> f64 {
+        self.start + self.duration
+    }
+
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    pub fn velocity(&self) -> u8 {
+        self.velocity
+    }
+
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    /// Whether the note sounds at `time`, counting its start but not its end.
+    pub fn sounds_at(&self, time: f64) -> bool {
+        self.start <= time && time < self.end()
+    }
+
+    /// The note-on and note-off, with their times.
+    pub fn messages(&self) -> [(f64, MidiMsg); 2] {
+        [
+            (
+                self.start,
+                midi_msg_from(self.channel, self.pitch, self.velocity),
+            ),
+            (self.end(), midi_msg_from(self.channel, self.pitch, 0)),
+        ]
+    }
+}
+
+/// A chord from `chords_starts_durations`, heard from `start` for
+/// `duration` seconds.
+#[derive(Copy, Clone, Debug)]
+pub struct ChordSpan {
+    chord: Chord,
+    start: f64,
+    duration: f64,
+}
+
+impl ChordSpan {
+    pub fn chord(&self) -> Chord {
+        self.chord
+    }
+
+    pub fn start(&self) -> f64 {
+        self.start
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.duration
+    }
+
+    pub fn end(&self) -> f64 {
+        self.start + self.duration
+    }
+}
+
+fn channel_of(msg: &MidiMsg) -> Option<Channel> {
+    match msg {
+        MidiMsg::ChannelVoice { channel, .. } => Some(*channel),
+        _ => None,
+    }
+}
+
+fn note_of(msg: &MidiMsg) -> Option<u8> {
+    match msg {
+        MidiMsg::ChannelVoice {
+            msg: ChannelVoiceMsg::NoteOn { note, .. } | ChannelVoiceMsg::NoteOff { note, .. },
+            ..
+        } => Some(*note),
+        _ => None,
+    }
+}
+
+/// `msg` with its note replaced, if it is a note-on or note-off.
+fn with_note(msg: &MidiMsg, note: u8) -> MidiMsg {
+    match msg {
+        MidiMsg::ChannelVoice {
+            channel,
+            msg: ChannelVoiceMsg::NoteOn { velocity, .. },
+        } => MidiMsg::ChannelVoice {
+            channel: *channel,
+            msg: ChannelVoiceMsg::NoteOn {
+                note,
+                velocity: *velocity,
+            },
+        },
+        MidiMsg::ChannelVoice {
+            channel,
+            msg: ChannelVoiceMsg::NoteOff { velocity, .. },
+        } => MidiMsg::ChannelVoice {
+            channel: *channel,
+            msg: ChannelVoiceMsg::NoteOff {
+                note,
+                velocity: *velocity,
+            },
+        },
+        _ => msg.clone(),
+    }
+}
+
+/// The notes a transposition could not move, as (time, original pitch)
+/// pairs: they would leave the MIDI range, or have no matching degree in
+/// the target scale.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutOfRange {
+    notes: Vec<(f64, u8)>,
+}
+
+impl OutOfRange {
+    pub fn notes(&self) -> &[(f64, u8)] {
+        &self.notes
+    }
+}
+
+impl Display for OutOfRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} notes could not be transposed:", self.notes.len())?;
+        for (time, pitch) in self.notes.iter() {
+            write!(f, " {} at {time:.2}", Pitch::from_midi(*pitch))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for OutOfRange {}
+
+/// Why text could not be read as a note, pitch, chord, scale or key, with
+/// the text in question.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ParseError {
+    MissingNoteName,
+    NotANoteLetter { letter: char, text: String },
+    TooManyAccidentals(String),
+    TextAfterNoteName { rest: String, text: String },
+    MissingOctave(String),
+    UnknownChordQuality { quality: String, text: String },
+    NoPitches(String),
+    BassNotLowest { lowest: u8, text: String },
+    PitchOutOfRange(String),
+    UnknownScale(String),
+    NotAStep(String),
+    StepsNotAnOctave(String),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::MissingNoteName => write!(f, "Expected a note name, found nothing"),
+            ParseError::NotANoteLetter { letter, text } => {
+                write!(f, "\"{letter}\" is not a note letter in \"{text}\"")
+            }
+            ParseError::TooManyAccidentals(text) => {
+                write!(f, "Too many accidentals in \"{text}\"")
+            }
+            ParseError::TextAfterNoteName { rest, text } => {
+                write!(f, "Unexpected \"{rest}\" after note name in \"{text}\"")
+            }
+            ParseError::MissingOctave(text) => {
+                write!(f, "Expected an octave number after the note in \"{text}\"")
+            }
+            ParseError::UnknownChordQuality { quality, text } => {
+                write!(f, "Unrecognized chord quality \"{quality}\" in \"{text}\"")
+            }
+            ParseError::NoPitches(text) => write!(f, "No pitches in chord \"{text}\""),
+            ParseError::BassNotLowest { lowest, text } => write!(
+                f,
+                "{} is not the lowest pitch in \"{text}\"",
+                Pitch::from_midi(*lowest)
+            ),
+            ParseError::PitchOutOfRange(pitch) => {
+                write!(f, "Pitch {pitch} is outside the MIDI range")
+            }
+            ParseError::UnknownScale(text) => write!(f, "Unrecognized scale \"{text}\""),
+            ParseError::NotAStep(step) => write!(f, "\"{step}\" is not a step size"),
+            ParseError::StepsNotAnOctave(steps) => {
+                write!(f, "Steps of \"{steps}\" must be positive and total 12")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 #[derive(Copy, Clone, Default, Eq, PartialEq)]
 pub struct ReducedPitches {
     on: u16,
@@ -557,6 +1916,14 @@ impl ReducedPitches {
         (0..12).filter(|p| self.on & (1 << p) > 0)
     }
 
+    pub fn intervals_above(&self, root: u8) -> u16 {
+        let mut result = 0;
+        for pitch in self.iter() {
+            result |= 1 << ((pitch + 12 - root) % 12);
+        }
+        result
+    }
+
     pub fn pitches_diffs(&self) -> (Vec<u8>, Vec<u8>) {
         let mut diffs = Vec::new();
         let pitches = self.iter().collect::<Vec<_>>();
@@ -576,17 +1943,58 @@ fn major_or_minor_third(interval: u8) -> bool {
     interval == 3 || interval == 4
 }
 
-fn first_third_index(diffs: &[u8]) -> Option<usize> {
-    let mut i = 0;
-    while i < diffs.len() {
-        if major_or_minor_third(diffs[i]) && major_or_minor_third(diffs[(i + 1) % diffs.len()]) {
-            return Some(i);
+/// Triads formed by two consecutive stacked thirds, ignoring any other
+/// pitch classes that are present.
+fn stacked_triads(reduced: ReducedPitches) -> Vec<(u8, ChordMode)> {
+    let (pitches, diffs) = reduced.pitches_diffs();
+    let mut result = vec![];
+    for i in 0..diffs.len() {
+        let first = diffs[i];
+        let second = diffs[(i + 1) % diffs.len()];
+        if major_or_minor_third(first) && major_or_minor_third(second) {
+            let mode = match (first, second) {
+                (3, 3) => ChordMode::Diminished,
+                (3, _) => ChordMode::Minor,
+                (_, 3) => ChordMode::Major,
+                _ => ChordMode::Augmented,
+            };
+            result.push((pitches[i], mode));
         }
-        i += 1;
     }
-    None
+    result
+}
+
+pub fn transposed_from(recording: &Recording, semitones: i16) -> Result<Recording, OutOfRange> {
+    Ok(PitchSequence::new(recording)
+        .transposed(semitones)?
+        .recording())
+}
+
+pub fn transposed_diatonic_from(
+    recording: &Recording,
+    key: Key,
+    steps: i32,
+) -> Result<Recording, OutOfRange> {
+    Ok(PitchSequence::new(recording)
+        .transposed_diatonic(key, steps)?
+        .recording())
 }
 
+pub fn transposed_to_from(
+    recording: &Recording,
+    from: Key,
+    to: Key,
+) -> Result<Recording, OutOfRange> {
+    Ok(PitchSequence::new(recording)
+        .transposed_to(from, to)?
+        .recording())
+}
+
+/// The recording read as a single melodic line: each entry is a duration,
+/// a pitch and a velocity, with a velocity of 0 for a rest after the pitch
+/// is released. A note struck while another sounds cuts the other short, so
+/// chords come out as rapid successions of notes; `notes_from` keeps every
+/// note of polyphonic input intact.
 pub fn durations_notes_from(recording: &Recording) -> Vec<(f64, u8, u8)> {
     let mut result = Vec::new();
     let mut queue = recording.midi_queue();
@@ -617,6 +2025,24 @@ pub fn durations_notes_from(recording: &Recording) -> Vec<(f64, u8, u8)> {
     result
 }
 
+pub fn notes_from(recording: &Recording) -> Vec<Note> {
+    PitchSequence::new(recording).notes()
+}
+
+/// The notes of `melody`, in the form returned by `durations_notes_from`,
+/// played on `channel` from time `start`. Rests are dropped.
+pub fn notes_from_melody(melody: &[(f64, u8, u8)], start: f64, channel: Channel) -> Vec<Note> {
+    let mut time = start;
+    let mut result = vec![];
+    for (duration, pitch, velocity) in melody.iter() {
+        if *velocity > 0 {
+            result.push(Note::new(time, *duration, *pitch, *velocity, channel));
+        }
+        time += duration;
+    }
+    result
+}
+
 fn find_first_note(queue: &mut VecDeque<(f64, MidiMsg)>) -> Option<(f64, u8, u8)> {
     while let Some((time, msg)) = queue.pop_front() {
         if let Some((n, v)) = note_velocity_from(&msg) {
@@ -742,10 +2168,22 @@ mod tests {
     use std::collections::BTreeSet;
 
     use midi_msg::Channel;
-    use midi_note_recorder::{midi_msg_from, Recording};
+    use midi_note_recorder::{midi_msg_from, note_velocity_from, Recording};
     use rand::Rng;
 
-    use crate::{Accidental, ActivePitches, NoteLetter, NoteName, PitchSequence, ScaleMode};
+    use crate::{
+        durations_notes_from, notes_from, notes_from_melody, transposed_from, Accidental,
+        ActivePitches, Chord, ChordContext, ChordMode, ChordName, Inversion, Key, Note, NoteLetter,
+        NoteName, Pitch, PitchSequence, ReducedPitches, Scale, ScaleMode,
+    };
+
+    fn active_from(pitches: &[u8]) -> ActivePitches {
+        let mut active = ActivePitches::default();
+        for pitch in pitches {
+            active.update_from(&midi_msg_from(Channel::Ch1, *pitch, 100));
+        }
+        active
+    }
 
     #[test]
     fn test_ascending_scale() {
@@ -827,6 +2265,228 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_new_scales() {
+        let c = NoteName::name_of(0);
+        for (mode, expected) in [
+            (ScaleMode::MajorPentatonic, vec![0, 2, 4, 7, 9]),
+            (ScaleMode::MinorPentatonic, vec![0, 3, 5, 7, 10]),
+            (ScaleMode::Blues, vec![0, 3, 5, 6, 7, 10]),
+            (ScaleMode::BebopDominant, vec![0, 2, 4, 5, 7, 9, 10, 11]),
+            (ScaleMode::BebopMajor, vec![0, 2, 4, 5, 7, 8, 9, 11]),
+            (ScaleMode::HungarianMinor, vec![0, 2, 3, 6, 7, 8, 11]),
+            (ScaleMode::DoubleHarmonic, vec![0, 1, 4, 5, 7, 8, 11]),
+            (ScaleMode::Hirajoshi, vec![0, 2, 3, 7, 8]),
+            (ScaleMode::InSen, vec![0, 1, 5, 7, 10]),
+        ] {
+            assert_eq!(mode.pitch_classes(c), expected);
+            assert_eq!(mode.num_notes(), expected.len());
+            let down = mode.notes_going_down(c).take(expected.len() + 1);
+            let mut down = down.map(|p| p % 12).collect::<Vec<_>>();
+            down.reverse();
+            assert_eq!(down[..expected.len()], expected[..]);
+        }
+        let a = NoteName::new(NoteLetter::A, Accidental::Natural);
+        assert_eq!(ScaleMode::MinorPentatonic.note_up(a, 57, 3), Some(62));
+        assert_eq!(ScaleMode::Blues.note_down(a, 69, 4), Some(63));
+        assert!(ChordMode::Dominant7
+            .scales()
+            .contains(&ScaleMode::BebopDominant));
+        assert!(!ScaleMode::HungarianMinor.is_tonal());
+        assert!(ScaleMode::Locrian.is_tonal());
+    }
+
+    #[test]
+    fn test_custom_scale() {
+        let c = NoteName::name_of(0);
+        let major = Scale::from_steps(&[2, 2, 1, 2, 2, 2, 1]).unwrap();
+        assert_eq!(major, Scale::from(ScaleMode::Major));
+        assert_eq!(major.mask(), 0b1010_1011_0101);
+        assert_eq!(Scale::from_mask(0b1010_1011_0101), Some(major));
+        assert_eq!(
+            major.notes_going_up(c).take(15).collect::<Vec<_>>(),
+            ScaleMode::Major
+                .notes_going_up(c)
+                .take(15)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(major.note_down(c, 60, 3), Some(57));
+
+        let d = NoteName::new(NoteLetter::D, Accidental::Natural);
+        let reduced = ReducedPitches::new(active_from(&[62, 64, 65, 67, 69, 71, 72]));
+        let dorian = Scale::from_mask(reduced.intervals_above(2)).unwrap();
+        assert_eq!(dorian.steps(), vec![2, 1, 2, 2, 2, 1, 2]);
+        assert_eq!(dorian.pitch_classes(d), ScaleMode::Dorian.pitch_classes(d));
+
+        let melodic = Scale::from(ScaleMode::MelodicMinor);
+        assert_eq!(melodic.to_string(), "2 1 2 2 2 2 1 / 2 1 2 2 1 2 2");
+        assert_eq!(
+            major
+                .with_descending(&[2, 1, 2, 2, 1, 2, 2])
+                .unwrap()
+                .note_down(c, 72, 3),
+            Some(68)
+        );
+        assert_eq!(Scale::from_steps(&[2, 2, 2]), None);
+        assert_eq!(Scale::from_steps(&[0, 2, 2, 2, 2, 2, 2]), None);
+        assert_eq!(Scale::from_mask(0b10), None);
+    }
+
+    #[test]
+    fn test_scale_degrees() {
+        let c_major = Key::from_pitch_class(0, ScaleMode::Major);
+        let a_minor = Key::from_pitch_class(9, ScaleMode::Minor);
+        let c_pentatonic = Key::from_pitch_class(0, ScaleMode::MajorPentatonic);
+        for (key, pitch, expected) in [
+            (c_major, 60, "1"),
+            (c_major, 71, "7"),
+            (c_major, 61, "♭2"),
+            (c_major, 63, "♭3"),
+            (c_major, 66, "♯4"),
+            (c_major, 70, "♭7"),
+            (a_minor, 68, "♯7"),
+            (c_pentatonic, 65, "♯3"),
+            (c_pentatonic, 66, "♭4"),
+        ] {
+            assert_eq!(key.degree_of(pitch).to_string(), expected);
+        }
+
+        let a_pentatonic = Key::from_pitch_class(9, ScaleMode::MinorPentatonic);
+        for (key, pitch, steps, expected) in [
+            (c_major, 64, 2, Some(67)),
+            (c_major, 63, 2, Some(66)),
+            (c_major, 64, -2, Some(60)),
+            (c_major, 60, -1, Some(59)),
+            (c_major, 60, 0, Some(60)),
+            (c_major, 60, 7, Some(72)),
+            (c_major, 127, 1, None),
+            (c_major, 0, -1, None),
+            (a_pentatonic, 57, 1, Some(60)),
+            (a_pentatonic, 57, -1, Some(55)),
+        ] {
+            assert_eq!(key.transpose_diatonic(pitch, steps), expected);
+        }
+        assert_eq!(
+            ScaleMode::Major.note_up(NoteName::name_of(0), 60, 0),
+            Some(60)
+        );
+
+        assert_eq!(
+            c_major.stacked_thirds(60, 7),
+            vec![60, 64, 67, 71, 74, 77, 81]
+        );
+        let dominant = c_major.stacked_thirds(67, 4);
+        assert_eq!(
+            format!("{}", ChordName::new(active_from(&dominant)).unwrap()),
+            "G  Dominant7"
+        );
+        let supertonic = c_major.stacked_thirds(62, 3);
+        assert_eq!(
+            format!("{}", ChordName::new(active_from(&supertonic)).unwrap()),
+            "D  Minor"
+        );
+
+        assert_eq!(c_major.interval_between(60, 66).unwrap().to_string(), "A4");
+        assert_eq!(c_major.interval_between(60, 63).unwrap().to_string(), "m3");
+        assert_eq!(c_major.interval_between(67, 60).unwrap().to_string(), "P5");
+    }
+
+    fn melody(pitches: &[u8]) -> PitchSequence {
+        let mut seq = PitchSequence::default();
+        let mut current = ActivePitches::default();
+        for (i, pitch) in pitches.iter().enumerate() {
+            let t = i as f64;
+            seq.push(t, &midi_msg_from(Channel::Ch1, *pitch, 100), &mut current);
+            seq.push(
+                t + 0.5,
+                &midi_msg_from(Channel::Ch1, *pitch, 0),
+                &mut current,
+            );
+        }
+        seq
+    }
+
+    fn notes_of(seq: &PitchSequence) -> Vec<u8> {
+        seq.seq
+            .iter()
+            .filter_map(|(_, msg, _)| note_velocity_from(msg))
+            .filter(|(_, v)| *v > 0)
+            .map(|(n, _)| n)
+            .collect()
+    }
+
+    #[test]
+    fn test_transposition() {
+        let c_major = Key::from_pitch_class(0, ScaleMode::Major);
+        let seq = melody(&[60, 64, 67, 71, 63]);
+        assert_eq!(
+            notes_of(&seq.transposed(2).unwrap()),
+            vec![62, 66, 69, 73, 65]
+        );
+        assert_eq!(
+            notes_of(&seq.transposed(-60).unwrap()),
+            vec![0, 4, 7, 11, 3]
+        );
+        assert_eq!(
+            notes_of(&seq.transposed_diatonic(c_major, 1).unwrap()),
+            vec![62, 65, 69, 72, 64]
+        );
+        let c_dorian = Key::from_pitch_class(0, ScaleMode::Dorian);
+        let d_major = Key::from_pitch_class(2, ScaleMode::Major);
+        assert_eq!(
+            notes_of(&seq.transposed_to(c_major, c_dorian).unwrap()),
+            vec![60, 63, 67, 70, 62]
+        );
+        assert_eq!(
+            notes_of(&seq.transposed_to(c_major, d_major).unwrap()),
+            vec![62, 66, 69, 73, 65]
+        );
+        let c_pentatonic = Key::from_pitch_class(0, ScaleMode::MajorPentatonic);
+        assert_eq!(c_major.transpose_to(64, c_pentatonic), None);
+        assert_eq!(c_pentatonic.transpose_to(64, c_major), None);
+        let error = seq.transposed_to(c_major, c_pentatonic).err().unwrap();
+        assert_eq!(error.notes().len(), 5);
+
+        let error = seq.transposed(60).err().unwrap();
+        assert_eq!(error.notes(), &[(3.0, 71), (3.5, 71)]);
+        assert!(seq.transposed_diatonic(c_major, -40).is_err());
+
+        let recording = transposed_from(&seq.recording(), 12).unwrap();
+        assert_eq!(
+            notes_of(&PitchSequence::new(&recording)),
+            vec![72, 76, 79, 83, 75]
+        );
+    }
+
+    #[test]
+    fn test_pitch() {
+        for midi in 0..=127 {
+            assert_eq!(Pitch::from_midi(midi).midi(), Some(midi));
+        }
+        for (midi, expected) in [
+            (0, "C-1"),
+            (48, "C3"),
+            (60, "C4"),
+            (72, "C5"),
+            (75, "E♭5"),
+            (127, "G9"),
+        ] {
+            assert_eq!(format!("{}", Pitch::from_midi(midi)), expected);
+        }
+        let b_sharp = Pitch::new(NoteName::new(NoteLetter::B, Accidental::Sharp), 3);
+        let c_flat = Pitch::new(NoteName::new(NoteLetter::C, Accidental::Flat), 4);
+        assert_eq!(b_sharp.midi(), Some(60));
+        assert_eq!(c_flat.midi(), Some(59));
+        assert_eq!(
+            Pitch::new(NoteName::new(NoteLetter::C, Accidental::Flat), -1).midi(),
+            None
+        );
+        assert_eq!(
+            Pitch::new(NoteName::new(NoteLetter::A, Accidental::Natural), 9).midi(),
+            None
+        );
+    }
+
     #[test]
     fn test_active_pitches() {
         let mut rng = rand::thread_rng();
@@ -862,45 +2522,334 @@ mod tests {
     #[test]
     fn test_chord_id() {
         let recording = Recording::from_file("healing4").unwrap();
-        let expected = "A  Major ([61, 64, 69])
-A  Major ([61, 64, 69])
+        let expected = "A  Major/C♯ ([61, 64, 69])
+A  Power/E ([64, 69])
+A  Major/C♯ ([61, 64, 69])
 B  Major ([59, 63, 66])
-E♭ Minor ([58, 59, 63, 66])
+B  Major7/A♯ ([58, 59, 63, 66])
 B  Major ([59, 63, 66])
 B  Major ([59, 63, 66])
 B  Major ([59, 63, 66])
-E  Major ([59, 64, 68])
-E  Major ([59, 64, 68])
+E  Power/B ([59, 64])
+E  Major/B ([59, 64, 68])
+E  Major/B ([59, 64, 68])
+C♯ Power ([61, 68])
 C♯ Minor ([61, 64, 68])
 C♯ Minor ([61, 64, 68])
+C♯ Power ([61, 68])
 C♯ Minor ([61, 64, 68])
-A  Major ([61, 64, 69])
-A  Major ([61, 64, 69])
+C♯ Power ([61, 68])
+A  Major/C♯ ([61, 64, 69])
+A  Major/C♯ ([61, 64, 69])
 B  Major ([59, 63, 66])
 B  Major ([59, 63, 66])
-E  Major ([59, 64, 68])
-E  Major ([59, 64, 68])
+E  Major/B ([59, 64, 68])
+E  Major/B ([59, 64, 68])
+C♯ Power ([61, 68])
 C♯ Minor ([61, 64, 68])
 C♯ Minor ([61, 64, 68])
 C♯ Minor ([61, 64, 68])
-A  Major ([61, 64, 69])
-A  Major ([61, 64, 69])
+A  Major/C♯ ([61, 64, 69])
+A  Major/C♯ ([61, 64, 69])
 B  Major ([59, 63, 66])
 B  Major ([59, 63, 66])
 B  Major ([59, 63, 66])
-E  Major ([59, 64, 68])
-E  Major ([59, 64, 68])
+E  Power/B ([59, 64])
+E  Major/B ([59, 64, 68])
+E  Major/B ([59, 64, 68])
 C♯ Minor ([61, 64, 68])
 C♯ Minor ([61, 64, 68])
 C♯ Minor ([61, 64, 68])
 A  Major ([57, 61, 64])
 B  Major ([59, 63, 66])
-E♭ Diminished ([57, 59, 63, 66])
+B  Dominant7/A ([57, 59, 63, 66])
 B  Major ([59, 63, 66])
-B  Major ([59, 63, 66])";
+B  Major ([59, 63, 66])
+B  Power ([59, 66])";
         let chords = PitchSequence::new(&recording).chords_starts_durations();
         for (i, chord_str) in expected.lines().enumerate() {
-            assert_eq!(format!("{}", chords[i].1), chord_str);
+            assert_eq!(format!("{}", chords[i].0), chord_str);
+        }
+    }
+
+    #[test]
+    fn test_seventh_chord_id() {
+        for (pitches, expected) in [
+            (vec![55, 59, 62, 65], "G  Dominant7"),
+            (vec![48, 52, 55, 59], "C  Major7"),
+            (vec![57, 60, 64, 67], "A  Minor7"),
+            (vec![48, 57, 64, 67], "C  Major6"),
+            (vec![59, 62, 65, 69], "B  HalfDiminished7"),
+            (vec![47, 50, 53, 56], "B  Diminished7"),
+            (vec![48, 51, 55, 59], "C  MinorMajor7"),
+            (vec![48, 52, 58], "C  Dominant7"),
+            (vec![48, 52, 55, 58, 62], "C  Dominant9"),
+            (vec![48, 52, 57, 58], "C  Dominant13"),
+        ] {
+            let name = ChordName::new(active_from(&pitches)).unwrap();
+            assert_eq!(format!("{name}"), expected);
+        }
+    }
+
+    #[test]
+    fn test_non_tertian_chord_id() {
+        for (pitches, expected) in [
+            (vec![60, 62, 67], "C  Sus2"),
+            (vec![60, 65, 67], "C  Sus4"),
+            (vec![55, 60, 62, 65], "G  Dominant7Sus4"),
+            (vec![40, 47], "E  Power"),
+            (vec![40, 47, 52], "E  Power"),
+            (vec![60, 62, 64, 67], "C  Add9"),
+            (vec![57, 59, 60, 64], "A  MinorAdd9"),
+        ] {
+            let name = ChordName::new(active_from(&pitches)).unwrap();
+            assert_eq!(format!("{name}"), expected);
+        }
+        assert_eq!(ChordName::new(active_from(&[60, 72])), None);
+    }
+
+    #[test]
+    fn test_chord_candidates() {
+        let active = active_from(&[58, 59, 63, 66]);
+        let candidates = ChordName::candidates(active, &ChordContext::default());
+        let names = candidates
+            .iter()
+            .map(|c| format!("{}", c.name()))
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["B  Major7", "B  Major", "E♭ Minor"]);
+        let total = candidates.iter().map(|c| c.score()).sum::<f64>();
+        assert!((total - 1.0).abs() < 1e-10);
+
+        let root_position = active_from(&[45, 57, 60, 64, 67]);
+        let sixth = active_from(&[48, 57, 64, 67]);
+        let context = ChordContext::default();
+        assert_eq!(
+            format!("{}", ChordName::best(root_position, &context).unwrap()),
+            "A  Minor7"
+        );
+        assert_eq!(
+            format!("{}", ChordName::best(sixth, &context).unwrap()),
+            "C  Major6"
+        );
+
+        let ambiguous = active_from(&[62, 67, 72]);
+        assert_eq!(format!("{}", ChordName::new(ambiguous).unwrap()), "G  Sus4");
+        let mut context = ChordContext::new(ChordName::new(active_from(&[55, 59, 62])));
+        context.update_from(&midi_msg_from(Channel::Ch1, 62, 20));
+        context.update_from(&midi_msg_from(Channel::Ch1, 67, 20));
+        context.update_from(&midi_msg_from(Channel::Ch1, 72, 127));
+        let name = ChordName::best(ambiguous, &context).unwrap();
+        assert_eq!(format!("{name}"), "C  Sus2");
+    }
+
+    #[test]
+    fn test_inversions() {
+        for (pitches, inversion, expected) in [
+            (vec![48, 52, 55], Inversion::Root, "C  Major ([48, 52, 55])"),
+            (
+                vec![52, 55, 60],
+                Inversion::First,
+                "C  Major/E ([52, 55, 60])",
+            ),
+            (
+                vec![55, 60, 64],
+                Inversion::Second,
+                "C  Major/G ([55, 60, 64])",
+            ),
+            (
+                vec![53, 55, 59, 62],
+                Inversion::Third,
+                "G  Dominant7/F ([53, 55, 59, 62])",
+            ),
+            (
+                vec![61, 64, 69],
+                Inversion::First,
+                "A  Major/C♯ ([61, 64, 69])",
+            ),
+            (
+                vec![51, 55, 60],
+                Inversion::First,
+                "C  Minor/E♭ ([51, 55, 60])",
+            ),
+        ] {
+            let active = active_from(&pitches);
+            let chord = Chord {
+                name: ChordName::new(active).unwrap(),
+                notes: active,
+            };
+            assert_eq!(chord.bass(), pitches[0]);
+            assert_eq!(chord.inversion(), inversion);
+            assert_eq!(format!("{chord}"), expected);
+        }
+        let chord = Chord {
+            name: ChordName::new(active_from(&[61, 64, 69])).unwrap(),
+            notes: active_from(&[61, 64, 69]),
+        };
+        assert_eq!(format!("{chord:#}"), "A  Major/C♯ (C♯4, E4, A4)");
+    }
+
+    #[test]
+    fn test_sixth_in_bass() {
+        // The same pitches would be named A minor 7 and A half-diminished 7,
+        // so the added-sixth chords are named explicitly.
+        for (mode, pitches) in [
+            (ChordMode::Major6, [57, 60, 64, 67]),
+            (ChordMode::Minor6, [57, 60, 63, 67]),
+        ] {
+            let chord = Chord {
+                name: ChordName {
+                    note: NoteLetter::C,
+                    accidental: Accidental::Natural,
+                    mode,
+                },
+                notes: active_from(&pitches),
+            };
+            assert_eq!(chord.inversion(), Inversion::Other);
+        }
+        let diminished = active_from(&[57, 60, 63, 66]);
+        let chord = Chord {
+            name: ChordName {
+                note: NoteLetter::C,
+                accidental: Accidental::Natural,
+                mode: ChordMode::Diminished7,
+            },
+            notes: diminished,
+        };
+        assert_eq!(chord.inversion(), Inversion::Third);
+    }
+
+    #[test]
+    fn test_spelling() {
+        let d_major = Key::new(
+            NoteName::new(NoteLetter::D, Accidental::Natural),
+            ScaleMode::Major,
+        );
+        let e_flat_minor = Key::new(
+            NoteName::new(NoteLetter::E, Accidental::Flat),
+            ScaleMode::Minor,
+        );
+        let c_major = Key::from_pitch_class(0, ScaleMode::Major);
+        let a_minor = Key::from_pitch_class(9, ScaleMode::Minor);
+        let g_sharp_major = Key::new(
+            NoteName::new(NoteLetter::G, Accidental::Sharp),
+            ScaleMode::Major,
+        );
+        for (key, signature) in [
+            (d_major, 2),
+            (e_flat_minor, -6),
+            (c_major, 0),
+            (g_sharp_major, 8),
+        ] {
+            assert_eq!(key.signature(), signature);
+        }
+        for (key, pitch, expected) in [
+            (d_major, 66, "F♯"),
+            (e_flat_minor, 66, "G♭"),
+            (e_flat_minor, 59, "C♭"),
+            (c_major, 61, "D♭"),
+            (c_major, 63, "E♭"),
+            (c_major, 66, "F♯"),
+            (c_major, 70, "B♭"),
+            (a_minor, 68, "G♯"),
+            (g_sharp_major, 67, "F𝄪"),
+        ] {
+            assert_eq!(key.spell(pitch).to_string().trim_end(), expected);
+        }
+        assert_eq!(e_flat_minor.spell_pitch(59).to_string(), "C♭4");
+        assert_eq!(
+            Pitch::spelled(60, NoteName::new(NoteLetter::B, Accidental::Sharp))
+                .unwrap()
+                .to_string(),
+            "B♯3"
+        );
+        assert_eq!(
+            Pitch::spelled(60, NoteName::new(NoteLetter::D, Accidental::Natural)),
+            None
+        );
+
+        let diminished = active_from(&[60, 63, 66, 69]);
+        let chord = Chord {
+            name: ChordName::new(diminished).unwrap(),
+            notes: diminished,
+        };
+        let spelled = chord
+            .spelled_pitches()
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(spelled, vec!["C4", "E♭4", "G♭4", "B𝄫4"]);
+
+        let g_flat = ChordName::new(active_from(&[54, 58, 61])).unwrap();
+        assert_eq!(format!("{g_flat}"), "F♯ Major");
+        assert_eq!(format!("{}", e_flat_minor.spell_chord(g_flat)), "G♭ Major");
+    }
+
+    #[test]
+    fn test_notes() {
+        let mut seq = PitchSequence::default();
+        let mut current = ActivePitches::default();
+        for (time, channel, pitch, velocity) in [
+            (0.0, Channel::Ch1, 60, 100),
+            (0.5, Channel::Ch2, 60, 80),
+            (1.0, Channel::Ch1, 60, 0),
+            // Struck again before it is released.
+            (1.0, Channel::Ch2, 60, 90),
+            (1.25, Channel::Ch2, 60, 0),
+            (1.5, Channel::Ch1, 64, 70),
+            (2.0, Channel::Ch2, 60, 0),
+            (2.5, Channel::Ch2, 60, 0),
+        ] {
+            seq.push(time, &midi_msg_from(channel, pitch, velocity), &mut current);
         }
+        let expected = [
+            Note::new(0.0, 1.0, 60, 100, Channel::Ch1),
+            Note::new(0.5, 0.75, 60, 80, Channel::Ch2),
+            Note::new(1.0, 1.0, 60, 90, Channel::Ch2),
+            // Never released, so it lasts until the end.
+            Note::new(1.5, 1.0, 64, 70, Channel::Ch1),
+        ];
+        let notes = seq.notes();
+        assert_eq!(notes, expected);
+        assert!(notes[1].sounds_at(0.5));
+        assert!(!notes[1].sounds_at(1.25));
+        assert_eq!(notes[2].end(), 2.0);
+
+        // The note-off at 1.0 comes before the note-on it coincides with.
+        let repeated = [
+            Note::new(0.0, 1.0, 62, 100, Channel::Ch1),
+            Note::new(1.0, 1.0, 62, 90, Channel::Ch1),
+        ];
+        assert_eq!(PitchSequence::from_notes(&repeated).notes(), repeated);
+
+        let melody = [(0.5, 60, 100), (0.25, 60, 0), (0.25, 62, 90), (0.0, 62, 0)];
+        assert_eq!(
+            notes_from_melody(&melody, 1.0, Channel::Ch3),
+            [
+                Note::new(1.0, 0.5, 60, 100, Channel::Ch3),
+                Note::new(1.75, 0.25, 62, 90, Channel::Ch3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chordal_notes() {
+        // up_down plays block triads.
+        let recording = Recording::from_file("up_down").unwrap();
+        let notes = notes_from(&recording);
+        assert_eq!(notes.len(), 42);
+        let first_chord = notes
+            .iter()
+            .filter(|n| n.sounds_at(0.3))
+            .map(|n| n.pitch())
+            .collect::<Vec<_>>();
+        assert_eq!(first_chord, [64, 60, 67]);
+        assert!(notes[..3].iter().all(|n| (n.end() - 0.55).abs() < 0.01));
+        // The melodic reading cuts all but the last note of each chord short.
+        let melody = durations_notes_from(&recording);
+        assert_eq!(melody.iter().filter(|(_, _, v)| *v > 0).count(), 42);
+        assert_eq!(
+            melody.iter().filter(|(d, _, v)| *v > 0 && *d > 0.1).count(),
+            14
+        );
     }
 }
@@ -0,0 +1,351 @@
+use std::fmt::Display;
+
+use midi_note_recorder::{note_velocity_from, Recording};
+
+use crate::{beat_tracker::BeatGrid, correlation, Chord, PitchSequence};
+
+/// How close, in beats, an onset must lie to a third or a quarter of the
+/// beat to count as evidence for compound or simple meter. Onsets this close
+/// to the beat itself count for neither.
+const SUBDIVISION_TOLERANCE: f64 = 0.06;
+const ON_BEAT_TOLERANCE: f64 = 0.08;
+
+/// How close, as a fraction of a subdivision, an onset must lie to it for
+/// its accent to count.
+const ACCENT_TOLERANCE: f64 = 0.3;
+
+/// Expected accents on each half beat of a bar when the beat divides in
+/// two. In 6/8 the tracked beat is then the quarter note, three to the bar,
+/// and the second accent falls between beats.
+const SIMPLE_METERS: [(TimeSignature, &[f64]); 3] = [
+    (TimeSignature::THREE_FOUR, &[1.0, 0.0, 0.5, 0.0, 0.5, 0.0]),
+    (
+        TimeSignature::FOUR_FOUR,
+        &[1.0, 0.0, 0.25, 0.0, 0.5, 0.0, 0.25, 0.0],
+    ),
+    (TimeSignature::SIX_EIGHT, &[1.0, 0.0, 0.0, 0.5, 0.0, 0.0]),
+];
+
+/// Expected accents on each eighth note of a bar when the beat divides in
+/// three, the tracked beat being the dotted quarter.
+const COMPOUND_METERS: [(TimeSignature, &[f64]); 1] =
+    [(TimeSignature::SIX_EIGHT, &[1.0, 0.0, 0.0, 0.5, 0.0, 0.0])];
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TimeSignature {
+    numerator: u8,
+    denominator: u8,
+}
+
+impl TimeSignature {
+    pub const THREE_FOUR: TimeSignature = TimeSignature::new(3, 4);
+    pub const FOUR_FOUR: TimeSignature = TimeSignature::new(4, 4);
+    pub const SIX_EIGHT: TimeSignature = TimeSignature::new(6, 8);
+
+    pub const fn new(numerator: u8, denominator: u8) -> Self {
+        Self {
+            numerator,
+            denominator,
+        }
+    }
+
+    pub fn numerator(&self) -> u8 {
+        self.numerator
+    }
+
+    pub fn denominator(&self) -> u8 {
+        self.denominator
+    }
+}
+
+impl Display for TimeSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
+
+/// A time signature laid over a `BeatGrid`: every `beats_per_bar` beats
+/// starting from beat `first_downbeat` begins a bar. Beats before the first
+/// downbeat form a pickup, whose bar number is -1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Meter {
+    time_signature: TimeSignature,
+    beats: BeatGrid,
+    beats_per_bar: usize,
+    first_downbeat: usize,
+}
+
+impl Meter {
+    /// `None` if `beats_per_bar` is 0.
+    pub fn new(
+        time_signature: TimeSignature,
+        beats: BeatGrid,
+        beats_per_bar: usize,
+        first_downbeat: usize,
+    ) -> Option<Self> {
+        if beats_per_bar == 0 {
+            None
+        } else {
+            Some(Self {
+                time_signature,
+                beats,
+                beats_per_bar,
+                first_downbeat: first_downbeat % beats_per_bar,
+            })
+        }
+    }
+
+    pub fn time_signature(&self) -> TimeSignature {
+        self.time_signature
+    }
+
+    pub fn beat_grid(&self) -> &BeatGrid {
+        &self.beats
+    }
+
+    /// Tracked beats per bar: 3 for 6/8 tracked in quarter notes, 2 for 6/8
+    /// tracked in dotted quarters.
+    pub fn beats_per_bar(&self) -> usize {
+        self.beats_per_bar
+    }
+
+    /// The index into `beat_grid().beats()` of the first downbeat.
+    pub fn first_downbeat(&self) -> usize {
+        self.first_downbeat
+    }
+
+    /// The times of the downbeats within the beat grid.
+    pub fn bar_starts(&self) -> Vec<f64> {
+        self.beats
+            .beats()
+            .iter()
+            .skip(self.first_downbeat)
+            .step_by(self.beats_per_bar)
+            .copied()
+            .collect()
+    }
+
+    /// Where `time` falls, counting the first downbeat as bar 0.
+    pub fn bar_at(&self, time: f64) -> f64 {
+        (self.beats.beat_at(time) - self.first_downbeat as f64) / self.beats_per_bar as f64
+    }
+
+    /// The bar containing `time` and how many beats into it `time` falls.
+    pub fn bar_position(&self, time: f64) -> (i64, f64) {
+        let beat = self.beats.beat_at(time) - self.first_downbeat as f64;
+        let bar = (beat / self.beats_per_bar as f64).floor();
+        (bar as i64, beat - bar * self.beats_per_bar as f64)
+    }
+
+    /// `chords`, as returned by `chords_starts_durations`, with the bar each
+    /// starts in, the beat within that bar and the duration in beats.
+    pub fn chords_in_bars(&self, chords: &[(Chord, f64, f64)]) -> Vec<(Chord, i64, f64, f64)> {
+        chords
+            .iter()
+            .map(|(chord, start, duration)| {
+                let (bar, beat) = self.bar_position(*start);
+                let beats = self.beats.beat_at(start + duration) - self.beats.beat_at(*start);
+                (*chord, bar, beat, beats)
+            })
+            .collect()
+    }
+}
+
+pub fn meter_from(recording: &Recording) -> Option<Meter> {
+    PitchSequence::new(recording).meter()
+}
+
+/// Infers the meter of `seq` over the beats found by `beat_grid`. Onsets
+/// falling on thirds of the beat rather than halves or quarters make the
+/// meter compound. Each onset's accent, its velocity weighted by how long
+/// the note lasts, is then gathered on the eighth-note grid and correlated
+/// with the accent pattern of each candidate meter at every bar position;
+/// the best fit gives the time signature and the downbeats. `None` if no
+/// beat can be found.
+pub fn meter(seq: &PitchSequence) -> Option<Meter> {
+    meter_on(seq, seq.beat_grid()?)
+}
+
+/// As `meter`, over a beat grid already known.
+pub fn meter_on(seq: &PitchSequence, beats: BeatGrid) -> Option<Meter> {
+    let onsets = onsets_in_beats(seq, &beats);
+    let compound = is_compound(&onsets);
+    let (subdivisions, candidates) = if compound {
+        (3, &COMPOUND_METERS[..])
+    } else {
+        (2, &SIMPLE_METERS[..])
+    };
+    let accents = accents(&onsets, beats.beats().len(), subdivisions);
+
+    let mut best: Option<(f64, TimeSignature, usize, usize)> = None;
+    for (time_signature, template) in candidates.iter() {
+        for phase in (0..template.len()).step_by(subdivisions) {
+            let expected = (0..accents.len())
+                .map(|i| template[(i + template.len() - phase) % template.len()])
+                .collect::<Vec<_>>();
+            let fit = correlation(&accents, &expected);
+            if best.is_none_or(|(b, _, _, _)| fit > b + 1e-9) {
+                best = Some((
+                    fit,
+                    *time_signature,
+                    phase / subdivisions,
+                    template.len() / subdivisions,
+                ));
+            }
+        }
+    }
+    let (_, time_signature, first_downbeat, beats_per_bar) = best?;
+    Meter::new(time_signature, beats, beats_per_bar, first_downbeat)
+}
+
+/// The position in beats, velocity and length in beats of each note in
+/// `seq`. Notes that are never released have no length.
+fn onsets_in_beats(seq: &PitchSequence, beats: &BeatGrid) -> Vec<(f64, u8, f64)> {
+    seq.seq
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (time, msg, _))| {
+            let (_, velocity) = note_velocity_from(msg)?;
+            if velocity == 0 {
+                return None;
+            }
+            let start = beats.beat_at(*time);
+            let length = seq
+                .next_off_note_index(i)
+                .map_or(0.0, |j| beats.beat_at(seq.seq[j].0) - start);
+            Some((start, velocity, length))
+        })
+        .collect()
+}
+
+/// Whether more off-beat onsets fall on thirds of the beat than on halves
+/// and quarters.
+fn is_compound(onsets: &[(f64, u8, f64)]) -> bool {
+    let mut triple = 0;
+    let mut duple = 0;
+    for (position, _, _) in onsets.iter() {
+        let fraction = position - position.floor();
+        let near = |target: f64| (fraction - target).abs() < SUBDIVISION_TOLERANCE;
+        if fraction.min(1.0 - fraction) < ON_BEAT_TOLERANCE {
+            continue;
+        }
+        if near(1.0 / 3.0) || near(2.0 / 3.0) {
+            triple += 1;
+        } else if near(0.25) || near(0.5) || near(0.75) {
+            duple += 1;
+        }
+    }
+    triple > duple
+}
+
+/// Accents summed on each of `subdivisions` parts of `num_beats` beats. A
+/// note's accent is its velocity, weighted up to fivefold by a length of up
+/// to two beats.
+fn accents(onsets: &[(f64, u8, f64)], num_beats: usize, subdivisions: usize) -> Vec<f64> {
+    let mut result = vec![0.0; num_beats * subdivisions];
+    for (position, velocity, length) in onsets.iter() {
+        let position = position * subdivisions as f64;
+        let nearest = position.round();
+        if (position - nearest).abs() < ACCENT_TOLERANCE && nearest >= 0.0 {
+            if let Some(accent) = result.get_mut(nearest as usize) {
+                *accent += *velocity as f64 / 127.0 * (0.5 + length.min(2.0));
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use midi_msg::Channel;
+
+    use crate::meter_finder::{meter, TimeSignature};
+    use crate::{Note, PitchSequence};
+
+    #[test]
+    fn test_waltz() {
+        // Bass and chord on the downbeat, lighter chords on two and three,
+        // after a one-beat pickup, at 120 beats per minute.
+        let mut notes = vec![Note::new(0.0, 0.2, 67, 60, Channel::Ch1)];
+        for bar in 0..12 {
+            let start = 0.5 + bar as f64 * 1.5;
+            notes.push(Note::new(start, 0.45, 48, 100, Channel::Ch1));
+            notes.push(Note::new(start, 0.45, 64, 90, Channel::Ch1));
+            notes.push(Note::new(start + 0.5, 0.2, 67, 60, Channel::Ch1));
+            notes.push(Note::new(start + 1.0, 0.2, 67, 60, Channel::Ch1));
+        }
+        let meter = meter(&PitchSequence::from_notes(&notes)).unwrap();
+        assert_eq!(meter.time_signature(), TimeSignature::THREE_FOUR);
+        assert_eq!(meter.beats_per_bar(), 3);
+        assert_eq!(meter.first_downbeat(), 1);
+        assert!((meter.bar_starts()[1] - 2.0).abs() < 0.02);
+        assert_eq!(meter.bar_position(0.0).0, -1);
+        let (bar, beat) = meter.bar_position(3.5 + 0.5);
+        assert_eq!(bar, 2);
+        assert!((beat - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_common_time() {
+        let mut notes = vec![];
+        for bar in 0..8 {
+            let start = bar as f64 * 2.0;
+            for (i, (velocity, duration)) in [(110, 0.45), (60, 0.2), (85, 0.4), (60, 0.2)]
+                .iter()
+                .enumerate()
+            {
+                notes.push(Note::new(
+                    start + i as f64 * 0.5,
+                    *duration,
+                    60,
+                    *velocity,
+                    Channel::Ch1,
+                ));
+            }
+        }
+        let meter = meter(&PitchSequence::from_notes(&notes)).unwrap();
+        assert_eq!(meter.time_signature(), TimeSignature::FOUR_FOUR);
+        assert_eq!(meter.first_downbeat(), 0);
+        assert_eq!(meter.time_signature().to_string(), "4/4");
+    }
+
+    /// Bars of six eighth notes, accented on the first and fourth, which
+    /// last `long` seconds against `short` for the others.
+    fn six_eight(start: f64, eighth: f64, bars: usize, long: f64, short: f64) -> PitchSequence {
+        let mut notes = vec![];
+        for bar in 0..bars {
+            let bar_start = start + bar as f64 * 6.0 * eighth;
+            for i in 0..6 {
+                let (velocity, length) = match i {
+                    0 => (110, long),
+                    3 => (90, long),
+                    _ => (50, short),
+                };
+                notes.push(Note::new(
+                    bar_start + i as f64 * eighth,
+                    length,
+                    60,
+                    velocity,
+                    Channel::Ch1,
+                ));
+            }
+        }
+        PitchSequence::from_notes(&notes)
+    }
+
+    #[test]
+    fn test_six_eight() {
+        // Eighths of 0.25 seconds put the beat on the quarter note.
+        let meter = meter(&six_eight(0.0, 0.25, 8, 0.24, 0.12)).unwrap();
+        assert_eq!(meter.time_signature(), TimeSignature::SIX_EIGHT);
+        assert_eq!(meter.beats_per_bar(), 3);
+        assert_eq!(meter.first_downbeat(), 0);
+
+        // Eighths of 0.2 seconds put the beat on the dotted quarter.
+        let meter = meter(&six_eight(0.3, 0.2, 10, 0.19, 0.15)).unwrap();
+        assert_eq!(meter.time_signature(), TimeSignature::SIX_EIGHT);
+        assert_eq!(meter.beats_per_bar(), 2);
+        assert_eq!(meter.first_downbeat(), 0);
+        assert!((meter.bar_starts()[0] - 0.3).abs() < 0.02);
+    }
+}
@@ -0,0 +1,305 @@
+//! MusicXML scores from a `PitchSequence`, quantized to a sixteenth-note
+//! grid at a fixed tempo and written as a single staff. Notes that sound
+//! together are written as chords; notes that outlast a barline, a chord
+//! change or a change in the other sounding notes are split and tied.
+
+use crate::notation::{Alterations, Score, Segment, DIVISIONS};
+use crate::{Accidental, Chord, ChordMode, Key, NoteName, PitchSequence, ScaleMode};
+
+/// A score in the key found by `PitchSequence::keys` unless `key` is given,
+/// with chord symbols from `chords_starts_durations`.
+pub fn musicxml_from_sequence(
+    seq: &PitchSequence,
+    key: Option<Key>,
+    beats_per_minute: f64,
+    beats_per_measure: u8,
+) -> String {
+    let key = key
+        .or_else(|| seq.keys().first().map(|(key, _)| *key))
+        .unwrap_or(Key::from_pitch_class(0, ScaleMode::Major));
+    musicxml_from(
+        seq,
+        &seq.chords_starts_durations(),
+        key,
+        beats_per_minute,
+        beats_per_measure,
+    )
+}
+
+/// A score of `seq` with chord symbols from `chords`, which need not come
+/// from `seq`: a melody from the `generator` functions, turned into a
+/// `PitchSequence` through `Recording::from_sequence`, can be written over
+/// the chords it was generated from. Pitches and chord roots are spelled in
+/// `key`, which also sets the key signature. Measures have
+/// `beats_per_measure` quarter notes.
+pub fn musicxml_from(
+    seq: &PitchSequence,
+    chords: &[(Chord, f64, f64)],
+    key: Key,
+    beats_per_minute: f64,
+    beats_per_measure: u8,
+) -> String {
+    musicxml_from_score(&Score::new(
+        seq,
+        chords,
+        key,
+        beats_per_minute,
+        beats_per_measure,
+    ))
+}
+
+fn musicxml_from_score(score: &Score) -> String {
+    let mut xml = String::new();
+    xml.push_str(HEADER);
+    for (i, measure) in score.measures.iter().enumerate() {
+        xml.push_str(&format!("    <measure number=\"{}\">\n", i + 1));
+        if i == 0 {
+            xml.push_str(&attributes(score));
+            xml.push_str(&format!(
+                "      <direction placement=\"above\">\n        <direction-type>\n          <metronome>\n            <beat-unit>quarter</beat-unit>\n            <per-minute>{}</per-minute>\n          </metronome>\n        </direction-type>\n        <sound tempo=\"{}\"/>\n      </direction>\n",
+                score.beats_per_minute.round(),
+                score.beats_per_minute
+            ));
+        }
+        let mut alterations = Alterations::new(score.signature_key);
+        for segment in measure.iter() {
+            if let Some(chord) = segment.chord {
+                xml.push_str(&harmony(&chord));
+            }
+            xml.push_str(&notes(segment, score.key, &mut alterations));
+        }
+        xml.push_str("    </measure>\n");
+    }
+    xml.push_str("  </part>\n</score-partwise>\n");
+    xml
+}
+
+const HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<!DOCTYPE score-partwise PUBLIC "-//Recordare//DTD MusicXML 4.0 Partwise//EN" "http://www.musicxml.org/dtds/partwise.dtd">
+<score-partwise version="4.0">
+  <part-list>
+    <score-part id="P1">
+      <part-name>Music</part-name>
+    </score-part>
+  </part-list>
+  <part id="P1">
+"#;
+
+/// Writes `segment` as tied notes of writable lengths, or as rests.
+fn notes(segment: &Segment, key: Key, alterations: &mut Alterations) -> String {
+    let mut result = String::new();
+    let lengths = segment.lengths();
+    for (i, length) in lengths.iter().enumerate() {
+        let mut note_type = format!("        <type>{}</type>\n", type_name(length.denominator));
+        if length.dotted {
+            note_type.push_str("        <dot/>\n");
+        }
+        let duration = length.sixteenths;
+        if segment.pitches.is_empty() {
+            result.push_str(&format!(
+                "      <note>\n        <rest/>\n        <duration>{duration}</duration>\n{note_type}      </note>\n"
+            ));
+        }
+        for (j, pitch) in segment.pitches.iter().enumerate() {
+            let stop = i > 0 || segment.tied_in.contains(pitch);
+            let start = i + 1 < lengths.len() || segment.tied_out.contains(pitch);
+            let spelled = key.spell_pitch(*pitch);
+            let alter = spelled.name().modifier().semitones();
+            let mut note = String::from("      <note>\n");
+            if j > 0 {
+                note.push_str("        <chord/>\n");
+            }
+            note.push_str(&format!(
+                "        <pitch>\n          <step>{:?}</step>\n",
+                spelled.name().letter()
+            ));
+            if alter != 0 {
+                note.push_str(&format!("          <alter>{alter}</alter>\n"));
+            }
+            note.push_str(&format!(
+                "          <octave>{}</octave>\n        </pitch>\n        <duration>{duration}</duration>\n",
+                spelled.octave()
+            ));
+            let ties = [(stop, "stop"), (start, "start")]
+                .into_iter()
+                .filter(|(tied, _)| *tied)
+                .collect::<Vec<_>>();
+            for (_, tie) in ties.iter() {
+                note.push_str(&format!("        <tie type=\"{tie}\"/>\n"));
+            }
+            note.push_str(&note_type);
+            if !stop && alterations.needs_accidental(spelled) {
+                note.push_str(&format!(
+                    "        <accidental>{}</accidental>\n",
+                    accidental_name(spelled.name().modifier())
+                ));
+            }
+            if !ties.is_empty() {
+                note.push_str("        <notations>\n");
+                for (_, tie) in ties.iter() {
+                    note.push_str(&format!("          <tied type=\"{tie}\"/>\n"));
+                }
+                note.push_str("        </notations>\n");
+            }
+            note.push_str("      </note>\n");
+            result.push_str(&note);
+        }
+    }
+    result
+}
+
+fn type_name(denominator: u8) -> &'static str {
+    match denominator {
+        1 => "whole",
+        2 => "half",
+        4 => "quarter",
+        8 => "eighth",
+        _ => "16th",
+    }
+}
+
+fn accidental_name(accidental: Accidental) -> &'static str {
+    match accidental {
+        Accidental::DoubleFlat => "flat-flat",
+        Accidental::Flat => "flat",
+        Accidental::Natural => "natural",
+        Accidental::Sharp => "sharp",
+        Accidental::DoubleSharp => "double-sharp",
+    }
+}
+
+fn attributes(score: &Score) -> String {
+    let (sign, line) = if score.is_bass() { ('F', 4) } else { ('G', 2) };
+    let mode = format!("{:?}", score.signature_key.mode()).to_lowercase();
+    format!(
+        "      <attributes>\n        <divisions>{DIVISIONS}</divisions>\n        <key>\n          <fifths>{}</fifths>\n          <mode>{mode}</mode>\n        </key>\n        <time>\n          <beats>{}</beats>\n          <beat-type>4</beat-type>\n        </time>\n        <clef>\n          <sign>{sign}</sign>\n          <line>{line}</line>\n        </clef>\n      </attributes>\n",
+        score.signature_key.signature(),
+        score.beats_per_measure
+    )
+}
+
+fn harmony(chord: &Chord) -> String {
+    let name = chord.name();
+    let (kind, degree) = kind_of(name.mode());
+    let mut result = format!(
+        "      <harmony>\n        <root>\n{}        </root>\n        <kind text=\"{}\">{kind}</kind>\n",
+        step_alter("root", name.root()),
+        name.mode().symbol()
+    );
+    if chord.bass() % 12 != name.root().pitch_class() {
+        result.push_str(&format!(
+            "        <bass>\n{}        </bass>\n",
+            step_alter("bass", chord.bass_name())
+        ));
+    }
+    if let Some((value, alter)) = degree {
+        result.push_str(&format!(
+            "        <degree>\n          <degree-value>{value}</degree-value>\n          <degree-alter>{alter}</degree-alter>\n          <degree-type>add</degree-type>\n        </degree>\n"
+        ));
+    }
+    result.push_str("      </harmony>\n");
+    result
+}
+
+fn step_alter(element: &str, name: NoteName) -> String {
+    let mut result = format!(
+        "          <{element}-step>{:?}</{element}-step>\n",
+        name.letter()
+    );
+    let alter = name.modifier().semitones();
+    if alter != 0 {
+        result.push_str(&format!(
+            "          <{element}-alter>{alter}</{element}-alter>\n"
+        ));
+    }
+    result
+}
+
+/// The MusicXML chord kind, with a degree to add for chords it has no
+/// kind for.
+fn kind_of(mode: ChordMode) -> (&'static str, Option<(u8, i8)>) {
+    match mode {
+        ChordMode::Major => ("major", None),
+        ChordMode::Minor => ("minor", None),
+        ChordMode::Diminished => ("diminished", None),
+        ChordMode::Augmented => ("augmented", None),
+        ChordMode::Dominant7 => ("dominant", None),
+        ChordMode::Major7 => ("major-seventh", None),
+        ChordMode::Minor7 => ("minor-seventh", None),
+        ChordMode::HalfDiminished7 => ("half-diminished", None),
+        ChordMode::Diminished7 => ("diminished-seventh", None),
+        ChordMode::MinorMajor7 => ("major-minor", None),
+        ChordMode::Major6 => ("major-sixth", None),
+        ChordMode::Minor6 => ("minor-sixth", None),
+        ChordMode::Dominant9 => ("dominant-ninth", None),
+        ChordMode::Major9 => ("major-ninth", None),
+        ChordMode::Minor9 => ("minor-ninth", None),
+        ChordMode::Dominant11 => ("dominant-11th", None),
+        ChordMode::Dominant13 => ("dominant-13th", None),
+        ChordMode::Dominant7Sus4 => ("suspended-fourth", Some((7, -1))),
+        ChordMode::Sus4 => ("suspended-fourth", None),
+        ChordMode::Sus2 => ("suspended-second", None),
+        ChordMode::Add9 => ("major", Some((9, 0))),
+        ChordMode::MinorAdd9 => ("minor", Some((9, 0))),
+        ChordMode::Power => ("power", None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use midi_msg::Channel;
+    use midi_note_recorder::midi_msg_from;
+
+    use crate::musicxml::musicxml_from;
+    use crate::{ActivePitches, Chord, ChordName, Key, PitchSequence, ScaleMode};
+
+    #[test]
+    fn test_musicxml() {
+        // At 120 beats per minute a sixteenth lasts 0.125 seconds.
+        let mut seq = PitchSequence::default();
+        let mut current = ActivePitches::default();
+        for (time, pitch, velocity) in [
+            (0.0, 60, 100),
+            (0.5, 60, 0),
+            (0.5, 63, 100),
+            (0.76, 63, 0),
+            (0.76, 63, 100),
+            (1.0, 63, 0),
+            (1.0, 67, 100),
+            (2.5, 67, 0),
+        ] {
+            seq.push(
+                time,
+                &midi_msg_from(Channel::Ch1, pitch, velocity),
+                &mut current,
+            );
+        }
+        let mut triad = ActivePitches::default();
+        for pitch in [48, 52, 55] {
+            triad.update_from(&midi_msg_from(Channel::Ch1, pitch, 100));
+        }
+        let chord = Chord {
+            name: ChordName::new(triad).unwrap(),
+            notes: triad,
+        };
+        let c_major = Key::from_pitch_class(0, ScaleMode::Major);
+        let xml = musicxml_from(&seq, &[(chord, 0.0, 2.0)], c_major, 120.0, 4);
+
+        assert!(xml.contains("<fifths>0</fifths>"));
+        assert!(xml.contains("<root-step>C</root-step>"));
+        assert!(xml.contains("<kind text=\"\">major</kind>"));
+        assert_eq!(xml.matches("<measure number=").count(), 2);
+        // The repeated E♭ needs its accidental only once in the measure.
+        assert_eq!(xml.matches("<step>E</step>").count(), 2);
+        assert_eq!(xml.matches("<accidental>flat</accidental>").count(), 1);
+        // G4, from 1.0 to 2.5 seconds, lasts 12 sixteenths or three quarters:
+        // a half in the first bar tied to a quarter in the second.
+        assert_eq!(xml.matches("<tie type=\"start\"/>").count(), 1);
+        assert_eq!(xml.matches("<tie type=\"stop\"/>").count(), 1);
+        assert!(xml.contains("<type>half</type>"));
+        let second = &xml[xml.find("<measure number=\"2\">").unwrap()..];
+        assert!(second.contains("<step>G</step>"));
+        assert!(second.contains("<rest/>"));
+        assert!(second.contains("<dot/>"));
+    }
+}
@@ -0,0 +1,299 @@
+//! The quantized form the score writers share: sounding pitches snapped to
+//! a sixteenth-note grid at a fixed tempo and cut into segments at every
+//! barline, chord change and change in the sounding pitches. Notes that
+//! outlast a segment are tied into the next.
+
+use std::collections::HashMap;
+
+use midi_msg::Channel;
+use midi_note_recorder::{midi_msg_from, note_velocity_from};
+
+use crate::{ActivePitches, Chord, Key, Pitch, PitchSequence, ScaleMode};
+
+/// Divisions of a quarter note: the grid is one sixteenth note.
+pub const DIVISIONS: u64 = 4;
+
+/// A length that can be written as one note: a whole, half, quarter,
+/// eighth or sixteenth note, possibly dotted.
+#[derive(Copy, Clone, Debug)]
+pub struct NoteLength {
+    pub sixteenths: u64,
+    pub denominator: u8,
+    pub dotted: bool,
+}
+
+const NOTE_LENGTHS: [NoteLength; 8] = [
+    NoteLength::new(1, false),
+    NoteLength::new(2, true),
+    NoteLength::new(2, false),
+    NoteLength::new(4, true),
+    NoteLength::new(4, false),
+    NoteLength::new(8, true),
+    NoteLength::new(8, false),
+    NoteLength::new(16, false),
+];
+
+impl NoteLength {
+    const fn new(denominator: u8, dotted: bool) -> Self {
+        let sixteenths = 16 / denominator as u64;
+        Self {
+            sixteenths: if dotted {
+                sixteenths * 3 / 2
+            } else {
+                sixteenths
+            },
+            denominator,
+            dotted,
+        }
+    }
+}
+
+/// A stretch of the grid over which the same pitches sound. `tied_in` holds
+/// the pitches held over from the previous segment rather than struck anew,
+/// and `tied_out` those held into the next. `chord` is set where a chord
+/// symbol starts.
+pub struct Segment {
+    pub start: u64,
+    pub length: u64,
+    pub pitches: Vec<u8>,
+    pub tied_in: Vec<u8>,
+    pub tied_out: Vec<u8>,
+    pub chord: Option<Chord>,
+}
+
+impl Segment {
+    /// `length` split into writable lengths, longest first, to be tied
+    /// together.
+    pub fn lengths(&self) -> Vec<NoteLength> {
+        let mut remaining = self.length;
+        let mut result = vec![];
+        while remaining > 0 {
+            let next = *NOTE_LENGTHS
+                .iter()
+                .find(|l| l.sixteenths <= remaining)
+                .unwrap();
+            result.push(next);
+            remaining -= next.sixteenths;
+        }
+        result
+    }
+}
+
+pub struct Score {
+    pub key: Key,
+    pub signature_key: Key,
+    pub beats_per_minute: f64,
+    pub beats_per_measure: u8,
+    pub measures: Vec<Vec<Segment>>,
+}
+
+impl Score {
+    /// Pitches are spelled in `key`, and chord roots in `chords` respelled
+    /// in it. Measures have `beats_per_measure` quarter notes.
+    pub fn new(
+        seq: &PitchSequence,
+        chords: &[(Chord, f64, f64)],
+        key: Key,
+        beats_per_minute: f64,
+        beats_per_measure: u8,
+    ) -> Self {
+        let ticks_per_second = beats_per_minute / 60.0 * DIVISIONS as f64;
+        let tick_of = |time: f64| (time.max(0.0) * ticks_per_second).round() as u64;
+        let beats_per_measure = beats_per_measure.max(1);
+        let measure_length = beats_per_measure as u64 * DIVISIONS;
+
+        let changes = pitch_changes(seq, tick_of);
+        let mut chord_starts: Vec<(u64, Chord)> = vec![];
+        for (chord, start, _) in chords.iter() {
+            let chord = Chord {
+                name: key.spell_chord(chord.name),
+                notes: chord.notes,
+            };
+            let tick = tick_of(*start);
+            if chord_starts.last().is_some_and(|(t, _)| *t == tick) {
+                chord_starts.pop();
+            }
+            if chord_starts.last().is_none_or(|(_, c)| *c != chord) {
+                chord_starts.push((tick, chord));
+            }
+        }
+
+        let end = changes.last().map_or(0, |(t, _, _)| *t);
+        let num_measures = end.div_ceil(measure_length).max(1);
+        let mut breaks = changes
+            .iter()
+            .map(|(t, _, _)| *t)
+            .chain(chord_starts.iter().map(|(t, _)| *t))
+            .chain((0..=num_measures).map(|m| m * measure_length))
+            .filter(|t| *t <= num_measures * measure_length)
+            .collect::<Vec<_>>();
+        breaks.sort();
+        breaks.dedup();
+
+        let mut segments: Vec<Segment> = vec![];
+        let mut previous = ActivePitches::default();
+        for window in breaks.windows(2) {
+            let (start, end) = (window[0], window[1]);
+            let (tick, sounding, struck) = changes
+                .iter()
+                .rev()
+                .find(|(t, _, _)| *t <= start)
+                .copied()
+                .unwrap_or_default();
+            let retriggered = |p: u8| tick == start && struck.is_active(p);
+            let tied_in = sounding
+                .iter()
+                .filter(|p| previous.is_active(*p) && !retriggered(*p))
+                .collect::<Vec<_>>();
+            if let Some(last) = segments.last_mut() {
+                last.tied_out = tied_in.clone();
+            }
+            segments.push(Segment {
+                start,
+                length: end - start,
+                pitches: sounding.iter().collect(),
+                tied_in,
+                tied_out: vec![],
+                chord: chord_starts
+                    .iter()
+                    .find(|(t, _)| *t == start)
+                    .map(|(_, c)| *c),
+            });
+            previous = sounding;
+        }
+
+        let mut measures = (0..num_measures).map(|_| vec![]).collect::<Vec<_>>();
+        for segment in segments {
+            measures[(segment.start / measure_length) as usize].push(segment);
+        }
+        Self {
+            key,
+            signature_key: signature_key(key),
+            beats_per_minute,
+            beats_per_measure,
+            measures,
+        }
+    }
+
+    /// `melody` is in the form returned by `durations_notes_from`, starting
+    /// at time `start` on the clock of `chords`.
+    pub fn from_melody(
+        melody: &[(f64, u8, u8)],
+        start: f64,
+        chords: &[(Chord, f64, f64)],
+        key: Key,
+        beats_per_minute: f64,
+        beats_per_measure: u8,
+    ) -> Self {
+        let mut seq = PitchSequence::default();
+        let mut current = ActivePitches::default();
+        let mut time = start;
+        for (duration, note, velocity) in melody.iter() {
+            seq.push(
+                time,
+                &midi_msg_from(Channel::Ch1, *note, *velocity),
+                &mut current,
+            );
+            time += duration;
+        }
+        Self::new(&seq, chords, key, beats_per_minute, beats_per_measure)
+    }
+
+    /// Bass clef if the notes lie mostly below middle C.
+    pub fn is_bass(&self) -> bool {
+        let pitches = self
+            .measures
+            .iter()
+            .flatten()
+            .flat_map(|s| s.pitches.iter())
+            .map(|p| *p as u64)
+            .collect::<Vec<_>>();
+        !pitches.is_empty() && pitches.iter().sum::<u64>() < 60 * pitches.len() as u64
+    }
+}
+
+/// The sounding pitches after each grid tick on which they change, with
+/// the pitches struck on that tick. Starts with silence at tick 0.
+fn pitch_changes<F: Fn(f64) -> u64>(
+    seq: &PitchSequence,
+    tick_of: F,
+) -> Vec<(u64, ActivePitches, ActivePitches)> {
+    let mut result = vec![(0, ActivePitches::default(), ActivePitches::default())];
+    for (time, msg, pitches) in seq.seq.iter() {
+        let tick = tick_of(*time);
+        let mut struck = ActivePitches::default();
+        if let Some((_, velocity)) = note_velocity_from(msg) {
+            if velocity > 0 {
+                struck.update_from(msg);
+            }
+        }
+        match result.last_mut() {
+            Some((t, p, r)) if *t == tick => {
+                *p = *pitches;
+                r.on |= struck.on;
+            }
+            _ => result.push((tick, *pitches, struck)),
+        }
+    }
+    result
+}
+
+/// The major, minor or church-mode key whose signature `key` is written
+/// with. Other seven-note scales, such as harmonic minor, take the
+/// signature of the major or minor key sharing their third.
+fn signature_key(key: Key) -> Key {
+    let key = key.heptatonic();
+    match key.mode() {
+        ScaleMode::Major
+        | ScaleMode::Minor
+        | ScaleMode::Dorian
+        | ScaleMode::Phrygian
+        | ScaleMode::Lydian
+        | ScaleMode::Mixolydian
+        | ScaleMode::Locrian => key,
+        _ if key
+            .pitch_classes()
+            .contains(&((key.tonic().pitch_class() + 3) % 12)) =>
+        {
+            Key::new(key.tonic(), ScaleMode::Minor)
+        }
+        _ => Key::new(key.tonic(), ScaleMode::Major),
+    }
+}
+
+/// The accidentals in force within one measure: the key signature's, until
+/// a note on the same letter and octave changes them.
+pub struct Alterations {
+    signature_key: Key,
+    written: HashMap<(usize, i8), i8>,
+}
+
+impl Alterations {
+    pub fn new(signature_key: Key) -> Self {
+        Self {
+            signature_key,
+            written: HashMap::new(),
+        }
+    }
+
+    /// Whether `pitch` needs an accidental written, recording it if so.
+    pub fn needs_accidental(&mut self, pitch: Pitch) -> bool {
+        let letter = pitch.name().letter();
+        let alter = pitch.name().modifier().semitones();
+        let expected = self
+            .written
+            .get(&(letter.index(), pitch.octave()))
+            .copied()
+            .unwrap_or_else(|| {
+                self.signature_key
+                    .spelled_scale()
+                    .iter()
+                    .find(|n| n.letter() == letter)
+                    .map_or(0, |n| n.modifier().semitones())
+            });
+        if alter != expected {
+            self.written.insert((letter.index(), pitch.octave()), alter);
+        }
+        alter != expected
+    }
+}
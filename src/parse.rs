@@ -0,0 +1,383 @@
+//! `FromStr` implementations. Each accepts what the matching `Display`
+//! implementation writes, along with the usual ASCII spellings: "F#",
+//! "Bb4", "F#m7b5/A", "D dorian".
+
+use std::str::FromStr;
+
+use enum_iterator::all;
+
+use crate::{
+    Accidental, ActivePitches, Chord, ChordMode, ChordName, Key, NoteLetter, NoteName, ParseError,
+    Pitch, Scale, ScaleMode,
+};
+
+/// Chord symbol suffixes accepted besides each `ChordMode::symbol()`.
+const CHORD_SYMBOL_ALIASES: [(&str, ChordMode); 28] = [
+    ("M", ChordMode::Major),
+    ("maj", ChordMode::Major),
+    ("min", ChordMode::Minor),
+    ("-", ChordMode::Minor),
+    ("°", ChordMode::Diminished),
+    ("o", ChordMode::Diminished),
+    ("+", ChordMode::Augmented),
+    ("M7", ChordMode::Major7),
+    ("Δ7", ChordMode::Major7),
+    ("Δ", ChordMode::Major7),
+    ("min7", ChordMode::Minor7),
+    ("-7", ChordMode::Minor7),
+    ("-7b5", ChordMode::HalfDiminished7),
+    ("ø", ChordMode::HalfDiminished7),
+    ("ø7", ChordMode::HalfDiminished7),
+    ("°7", ChordMode::Diminished7),
+    ("o7", ChordMode::Diminished7),
+    ("mM7", ChordMode::MinorMajor7),
+    ("mmaj7", ChordMode::MinorMajor7),
+    ("min6", ChordMode::Minor6),
+    ("M9", ChordMode::Major9),
+    ("Δ9", ChordMode::Major9),
+    ("min9", ChordMode::Minor9),
+    ("7sus", ChordMode::Dominant7Sus4),
+    ("sus", ChordMode::Sus4),
+    ("add2", ChordMode::Add9),
+    ("m(add9)", ChordMode::MinorAdd9),
+    ("(add9)", ChordMode::Add9),
+];
+
+/// Scale names accepted besides each `ScaleMode` variant name.
+const SCALE_ALIASES: [(&str, ScaleMode); 2] =
+    [("ionian", ScaleMode::Major), ("aeolian", ScaleMode::Minor)];
+
+impl FromStr for NoteName {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, rest) = split_note_name(s.trim())?;
+        if rest.is_empty() {
+            Ok(name)
+        } else {
+            Err(ParseError::TextAfterNoteName {
+                rest: rest.to_string(),
+                text: s.to_string(),
+            })
+        }
+    }
+}
+
+impl FromStr for Pitch {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, rest) = split_note_name(s.trim())?;
+        let octave = rest
+            .parse::<i8>()
+            .map_err(|_| ParseError::MissingOctave(s.to_string()))?;
+        Ok(Pitch::new(name, octave))
+    }
+}
+
+impl FromStr for ChordName {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (root, rest) = split_note_name(s.trim())?;
+        let rest = rest.trim();
+        let mode = all::<ChordMode>()
+            .find(|mode| format!("{mode:?}").eq_ignore_ascii_case(rest))
+            .or_else(|| chord_mode_from_symbol(rest))
+            .ok_or_else(|| ParseError::UnknownChordQuality {
+                quality: rest.to_string(),
+                text: s.to_string(),
+            })?;
+        Ok(ChordName {
+            note: root.letter,
+            accidental: root.modifier,
+            mode,
+        })
+    }
+}
+
+/// Accepts "NAME[/BASS] ([p1, p2, ...])", where the pitches may be MIDI
+/// numbers or written as in "C♯4". Without a pitch list the chord is voiced
+/// in close position from the root in octave 4, with its fifth even where
+/// that is optional, above any bass in octave 3.
+impl FromStr for Chord {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (symbol, pitches) = match s.trim_end().rsplit_once(" (") {
+            Some((symbol, pitches)) if pitches.ends_with(')') => {
+                (symbol, Some(&pitches[..pitches.len() - 1]))
+            }
+            _ => (s, None),
+        };
+        let (name, bass) = match symbol.split_once('/') {
+            Some((name, bass)) => (name.parse::<ChordName>()?, Some(bass.parse::<NoteName>()?)),
+            None => (symbol.parse::<ChordName>()?, None),
+        };
+        let mut notes = ActivePitches::default();
+        match pitches {
+            Some(pitches) => {
+                let pitches = pitches.trim_start_matches('[').trim_end_matches(']');
+                for pitch in pitches.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+                    notes.on |= 1 << midi_from(pitch)?;
+                }
+            }
+            None => {
+                if let Some(bass) = bass {
+                    notes.on |= 1 << (48 + bass.pitch_class());
+                }
+                let root = name.root().pitch_class();
+                let (required, optional) = name.mode.intervals();
+                let tones = required | (optional & (1 << 7));
+                for interval in (0..12).filter(|i| tones & (1 << i) != 0) {
+                    notes.on |= 1 << (60 + root + interval);
+                }
+            }
+        }
+        if notes.len() == 0 {
+            return Err(ParseError::NoPitches(s.to_string()));
+        }
+        let chord = Chord { name, notes };
+        if let Some(bass) = bass {
+            if chord.bass() % 12 != bass.pitch_class() {
+                return Err(ParseError::BassNotLowest {
+                    lowest: chord.bass(),
+                    text: s.to_string(),
+                });
+            }
+        }
+        Ok(chord)
+    }
+}
+
+impl FromStr for ScaleMode {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let wanted = s
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-' && *c != '_')
+            .collect::<String>();
+        all::<ScaleMode>()
+            .map(|mode| (format!("{mode:?}"), mode))
+            .chain(
+                SCALE_ALIASES
+                    .iter()
+                    .map(|(alias, mode)| (alias.to_string(), *mode)),
+            )
+            .find(|(name, _)| name.eq_ignore_ascii_case(&wanted))
+            .map(|(_, mode)| mode)
+            .ok_or_else(|| ParseError::UnknownScale(s.to_string()))
+    }
+}
+
+/// Accepts semitone steps separated by spaces or commas, optionally followed
+/// by a slash and the descending steps, or the name of a `ScaleMode`.
+impl FromStr for Scale {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(mode) = s.parse::<ScaleMode>() {
+            return Ok(Scale::from(mode));
+        }
+        let (up, down) = match s.split_once('/') {
+            Some((up, down)) => (up, Some(down)),
+            None => (s, None),
+        };
+        let scale = Scale::from_steps(&steps_from(up)?)
+            .ok_or_else(|| ParseError::StepsNotAnOctave(up.to_string()))?;
+        match down {
+            Some(down) => scale
+                .with_descending(&steps_from(down)?)
+                .ok_or_else(|| ParseError::StepsNotAnOctave(down.to_string())),
+            None => Ok(scale),
+        }
+    }
+}
+
+impl FromStr for Key {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (tonic, rest) = split_note_name(s.trim())?;
+        Ok(Key::new(tonic, rest.parse()?))
+    }
+}
+
+/// Reads a letter and any accidentals from the start of `s`, returning the
+/// note and the text after it.
+fn split_note_name(s: &str) -> Result<(NoteName, &str), ParseError> {
+    let mut chars = s.chars();
+    let first = chars.next().ok_or(ParseError::MissingNoteName)?;
+    let letter = all::<NoteLetter>()
+        .find(|letter| format!("{letter:?}").eq_ignore_ascii_case(&first.to_string()))
+        .ok_or_else(|| ParseError::NotANoteLetter {
+            letter: first,
+            text: s.to_string(),
+        })?;
+    let mut rest = chars.as_str();
+    let mut semitones = 0;
+    loop {
+        let mut chars = rest.chars();
+        semitones += match chars.next() {
+            Some('#') | Some('\u{266f}') => 1,
+            Some('b') | Some('\u{266d}') => -1,
+            Some('x') | Some('\u{1d12a}') => 2,
+            Some('\u{1d12b}') => -2,
+            Some('\u{266e}') => 0,
+            _ => break,
+        };
+        rest = chars.as_str();
+    }
+    let modifier = Accidental::from_semitones(semitones)
+        .ok_or_else(|| ParseError::TooManyAccidentals(s.to_string()))?;
+    Ok((NoteName::new(letter, modifier), rest))
+}
+
+fn steps_from(s: &str) -> Result<Vec<u8>, ParseError> {
+    s.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|step| !step.is_empty())
+        .map(|step| {
+            step.parse::<u8>()
+                .map_err(|_| ParseError::NotAStep(step.to_string()))
+        })
+        .collect()
+}
+
+fn chord_mode_from_symbol(symbol: &str) -> Option<ChordMode> {
+    let symbol = symbol.replace('\u{266d}', "b");
+    all::<ChordMode>()
+        .map(|mode| (mode.symbol().replace('\u{266d}', "b"), mode))
+        .chain(
+            CHORD_SYMBOL_ALIASES
+                .iter()
+                .map(|(alias, mode)| (alias.to_string(), *mode)),
+        )
+        .find(|(name, _)| *name == symbol)
+        .map(|(_, mode)| mode)
+}
+
+fn midi_from(pitch: &str) -> Result<u8, ParseError> {
+    let midi = match pitch.parse::<u8>() {
+        Ok(midi) => Some(midi),
+        Err(_) => pitch.parse::<Pitch>()?.midi(),
+    };
+    midi.filter(|m| *m <= 127)
+        .ok_or_else(|| ParseError::PitchOutOfRange(pitch.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Accidental, Chord, ChordMode, ChordName, Key, NoteLetter, NoteName, ParseError, Pitch,
+        Scale, ScaleMode,
+    };
+
+    #[test]
+    fn test_parse_note_names() {
+        for (text, letter, modifier) in [
+            ("F#", NoteLetter::F, Accidental::Sharp),
+            ("Bb", NoteLetter::B, Accidental::Flat),
+            ("e♭", NoteLetter::E, Accidental::Flat),
+            ("Gx", NoteLetter::G, Accidental::DoubleSharp),
+            ("Dbb", NoteLetter::D, Accidental::DoubleFlat),
+            ("C ", NoteLetter::C, Accidental::Natural),
+        ] {
+            assert_eq!(
+                text.parse::<NoteName>().unwrap(),
+                NoteName::new(letter, modifier)
+            );
+        }
+        assert_eq!(
+            "H".parse::<NoteName>(),
+            Err(ParseError::NotANoteLetter {
+                letter: 'H',
+                text: "H".to_string()
+            })
+        );
+        assert_eq!(
+            "C#m".parse::<NoteName>().unwrap_err().to_string(),
+            "Unexpected \"m\" after note name in \"C#m\""
+        );
+        assert!("Cbbb".parse::<NoteName>().is_err());
+
+        assert_eq!("Bb4".parse::<Pitch>().unwrap().midi(), Some(70));
+        assert_eq!("C-1".parse::<Pitch>().unwrap().midi(), Some(0));
+        assert!("C".parse::<Pitch>().is_err());
+        for midi in 0..=127 {
+            let pitch = Pitch::from_midi(midi);
+            assert_eq!(pitch.to_string().parse::<Pitch>().unwrap(), pitch);
+            assert_eq!(
+                pitch.name().to_string().parse::<NoteName>().unwrap(),
+                pitch.name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_chords() {
+        for (text, expected) in [
+            ("Cmaj7", "C  Major7"),
+            ("F#m7b5", "F♯ HalfDiminished7"),
+            ("Bbm", "B♭ Minor"),
+            ("G7sus4", "G  Dominant7Sus4"),
+            ("E5", "E  Power"),
+            ("Ab", "A♭ Major"),
+            ("D  Minor9", "D  Minor9"),
+        ] {
+            let name = text.parse::<ChordName>().unwrap();
+            assert_eq!(format!("{name}"), expected);
+            assert_eq!(name.symbol().parse::<ChordName>().unwrap(), name);
+            assert_eq!(expected.parse::<ChordName>().unwrap(), name);
+        }
+        assert!("Cfoo".parse::<ChordName>().is_err());
+
+        let chord = "F#m7b5/A".parse::<Chord>().unwrap();
+        assert_eq!(chord.name().mode(), ChordMode::HalfDiminished7);
+        assert_eq!(chord.bass(), 57);
+        assert_eq!(
+            format!("{chord}"),
+            "F♯ HalfDiminished7/A ([57, 66, 69, 72, 76])"
+        );
+        for text in [
+            "A  Major/C♯ ([61, 64, 69])",
+            "C  Major ([48, 52, 55])",
+            "G  Dominant7/F ([53, 55, 59, 62])",
+        ] {
+            assert_eq!(format!("{}", text.parse::<Chord>().unwrap()), text);
+        }
+        let spelled = "A  Major/C♯ (C♯4, E4, A4)".parse::<Chord>().unwrap();
+        assert_eq!(format!("{spelled:#}"), "A  Major/C♯ (C♯4, E4, A4)");
+        assert!("C/E ([48, 52, 55])".parse::<Chord>().is_err());
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        let d_dorian = "D dorian".parse::<Key>().unwrap();
+        assert_eq!(
+            d_dorian.tonic(),
+            NoteName::new(NoteLetter::D, Accidental::Natural)
+        );
+        assert_eq!(d_dorian.mode(), ScaleMode::Dorian);
+        assert_eq!(
+            "Bb harmonic minor".parse::<Key>().unwrap().mode(),
+            ScaleMode::HarmonicMinor
+        );
+        assert_eq!("A aeolian".parse::<Key>().unwrap().mode(), ScaleMode::Minor);
+        assert!("C bogus".parse::<Key>().is_err());
+        assert_eq!(
+            "2, 2, 3, 2, 3".parse::<Scale>().unwrap(),
+            Scale::from(ScaleMode::MajorPentatonic)
+        );
+        assert_eq!(
+            "dorian".parse::<Scale>().unwrap(),
+            Scale::from(ScaleMode::Dorian)
+        );
+        assert!("2 2 2".parse::<Scale>().is_err());
+        let melodic = Scale::from(ScaleMode::MelodicMinor);
+        assert_eq!(melodic.to_string().parse::<Scale>().unwrap(), melodic);
+        for text in ["E  Major", "E♭ Minor", "F♯ MelodicMinor"] {
+            assert_eq!(format!("{}", text.parse::<Key>().unwrap()), text);
+        }
+    }
+}
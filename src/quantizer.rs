@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+
+use midi_note_recorder::note_velocity_from;
+
+use crate::{beat_tracker::BeatGrid, PitchSequence};
+
+/// The positions within each beat that times snap to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Grid {
+    /// The beat divided into this many equal parts: 4 for straight
+    /// sixteenths, 3 for eighth-note triplets, 6 for sixteenth triplets.
+    Even(u8),
+    /// For each beat, whichever of these divisions puts its onsets closest
+    /// to the grid. Ties go to the earlier division, and beats without
+    /// onsets use the first. Empty means sixteenths.
+    Mixed(Vec<u8>),
+}
+
+impl Grid {
+    pub const SIXTEENTHS: Grid = Grid::Even(4);
+    pub const TRIPLETS: Grid = Grid::Even(3);
+
+    fn divisions(&self) -> &[u8] {
+        match self {
+            Grid::Even(division) => std::slice::from_ref(division),
+            Grid::Mixed(divisions) if !divisions.is_empty() => divisions,
+            Grid::Mixed(_) => &[4],
+        }
+    }
+}
+
+/// How far the onsets of a performance lay from the grid, in beats. A
+/// sequenced part scores 0; a careful human take typically stays within a
+/// few hundredths of a beat.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct QuantizationError {
+    onsets: usize,
+    mean: f64,
+    max: f64,
+}
+
+impl QuantizationError {
+    fn new(errors: &[f64]) -> Self {
+        if errors.is_empty() {
+            return Self::default();
+        }
+        Self {
+            onsets: errors.len(),
+            mean: errors.iter().sum::<f64>() / errors.len() as f64,
+            max: errors.iter().copied().fold(0.0, f64::max),
+        }
+    }
+
+    pub fn onsets(&self) -> usize {
+        self.onsets
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+}
+
+/// `seq` with every note on and off moved to the nearest grid position,
+/// keeping times in seconds. Notes too short to survive snapping last one
+/// grid step.
+pub fn quantized(
+    seq: &PitchSequence,
+    beats: &BeatGrid,
+    grid: &Grid,
+) -> (PitchSequence, QuantizationError) {
+    let positions = seq
+        .seq
+        .iter()
+        .map(|(time, _, _)| beats.beat_at(*time))
+        .collect::<Vec<_>>();
+    let onsets = seq
+        .seq
+        .iter()
+        .zip(positions.iter())
+        .filter(|((_, msg, _), _)| note_velocity_from(msg).is_some_and(|(_, v)| v > 0))
+        .map(|(_, position)| *position)
+        .collect::<Vec<_>>();
+    let divisions = BeatDivisions::new(&onsets, grid);
+
+    let mut started = [None; 128];
+    let mut events = vec![];
+    for ((_, msg, _), position) in seq.seq.iter().zip(positions.iter()) {
+        let mut snapped = divisions.snap(*position);
+        if let Some((note, velocity)) = note_velocity_from(msg) {
+            if velocity > 0 {
+                started[note as usize] = Some(snapped);
+            } else if let Some(start) = started[note as usize].take() {
+                if snapped <= start {
+                    snapped = start + divisions.step(start);
+                }
+            }
+        }
+        events.push((snapped, msg));
+    }
+    events.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+    let mut result = PitchSequence::default();
+    let mut current = Default::default();
+    for (position, msg) in events {
+        result.push(beats.time_at(position), msg, &mut current);
+    }
+    (result, divisions.error(&onsets))
+}
+
+/// `melody`, in the form returned by `durations_notes_from` and beginning at
+/// time `start`, with every note and rest boundary moved to the nearest grid
+/// position. Durations stay in seconds; notes last at least one grid step.
+pub fn quantized_melody(
+    melody: &[(f64, u8, u8)],
+    start: f64,
+    beats: &BeatGrid,
+    grid: &Grid,
+) -> (Vec<(f64, u8, u8)>, QuantizationError) {
+    let mut boundaries = vec![beats.beat_at(start)];
+    let mut time = start;
+    for (duration, _, _) in melody.iter() {
+        time += duration;
+        boundaries.push(beats.beat_at(time));
+    }
+    let onsets = melody
+        .iter()
+        .zip(boundaries.iter())
+        .filter(|((_, _, velocity), _)| *velocity > 0)
+        .map(|(_, position)| *position)
+        .collect::<Vec<_>>();
+    let divisions = BeatDivisions::new(&onsets, grid);
+
+    let mut snapped = vec![divisions.snap(boundaries[0])];
+    for ((_, _, velocity), end) in melody.iter().zip(boundaries.iter().skip(1)) {
+        let begin = *snapped.last().unwrap();
+        let shortest = if *velocity > 0 {
+            divisions.step(begin)
+        } else {
+            0.0
+        };
+        snapped.push(divisions.snap(*end).max(begin + shortest));
+    }
+    let result = melody
+        .iter()
+        .zip(snapped.windows(2))
+        .map(|((_, note, velocity), w)| {
+            (beats.time_at(w[1]) - beats.time_at(w[0]), *note, *velocity)
+        })
+        .collect();
+    (result, divisions.error(&onsets))
+}
+
+/// The division of each beat, as chosen by a `Grid` for a set of onsets.
+struct BeatDivisions {
+    chosen: HashMap<i64, u8>,
+    default: u8,
+}
+
+impl BeatDivisions {
+    /// `onsets` are positions in beats.
+    fn new(onsets: &[f64], grid: &Grid) -> Self {
+        let candidates = grid.divisions();
+        let mut by_beat: HashMap<i64, Vec<f64>> = HashMap::new();
+        for onset in onsets.iter() {
+            by_beat
+                .entry(onset.floor() as i64)
+                .or_default()
+                .push(*onset);
+        }
+        let chosen = by_beat
+            .iter()
+            .map(|(beat, onsets)| {
+                let best = candidates
+                    .iter()
+                    .map(|division| {
+                        let error = onsets
+                            .iter()
+                            .map(|onset| (snapped_to(*onset, *division) - onset).abs())
+                            .sum::<f64>();
+                        (*division, error)
+                    })
+                    .fold((candidates[0], f64::MAX), |best, (division, error)| {
+                        if error < best.1 - 1e-9 {
+                            (division, error)
+                        } else {
+                            best
+                        }
+                    });
+                (*beat, best.0)
+            })
+            .collect();
+        Self {
+            chosen,
+            default: candidates[0],
+        }
+    }
+
+    fn division(&self, position: f64) -> u8 {
+        *self
+            .chosen
+            .get(&(position.floor() as i64))
+            .unwrap_or(&self.default)
+    }
+
+    fn snap(&self, position: f64) -> f64 {
+        snapped_to(position, self.division(position))
+    }
+
+    fn step(&self, position: f64) -> f64 {
+        1.0 / self.division(position).max(1) as f64
+    }
+
+    fn error(&self, onsets: &[f64]) -> QuantizationError {
+        QuantizationError::new(
+            &onsets
+                .iter()
+                .map(|onset| (self.snap(*onset) - onset).abs())
+                .collect::<Vec<_>>(),
+        )
+    }
+}
+
+fn snapped_to(position: f64, division: u8) -> f64 {
+    let beat = position.floor();
+    let division = division.max(1) as f64;
+    beat + ((position - beat) * division).round() / division
+}
+
+#[cfg(test)]
+mod tests {
+    use midi_msg::Channel;
+    use midi_note_recorder::note_velocity_from;
+
+    use crate::beat_tracker::BeatGrid;
+    use crate::quantizer::{quantized, quantized_melody, Grid};
+    use crate::{Note, PitchSequence};
+
+    /// A note on channel 1 from `on` to `off` seconds.
+    fn note(on: f64, off: f64, pitch: u8) -> Note {
+        Note::new(on, off - on, pitch, 100, Channel::Ch1)
+    }
+
+    fn note_times(seq: &PitchSequence) -> Vec<(f64, u8, u8)> {
+        seq.seq
+            .iter()
+            .map(|(time, msg, _)| {
+                let (note, velocity) = note_velocity_from(msg).unwrap();
+                (*time, note, velocity)
+            })
+            .collect()
+    }
+
+    fn assert_close(actual: &[(f64, u8, u8)], expected: &[(f64, u8, u8)]) {
+        assert_eq!(actual.len(), expected.len());
+        for ((t1, n1, v1), (t2, n2, v2)) in actual.iter().zip(expected.iter()) {
+            assert!((t1 - t2).abs() < 1e-9, "{actual:?}");
+            assert_eq!((n1, v1), (n2, v2));
+        }
+    }
+
+    #[test]
+    fn test_sixteenths() {
+        // Half a second per beat.
+        let beats = BeatGrid::steady(120.0, 0.0, 8).unwrap();
+        let seq = PitchSequence::from_notes(&[
+            note(0.01, 0.24, 60),
+            note(0.2601, 0.5, 62),
+            note(0.62, 0.63, 64),
+        ]);
+        let (result, error) = quantized(&seq, &beats, &Grid::SIXTEENTHS);
+        assert_close(
+            &note_times(&result),
+            &[
+                (0.0, 60, 100),
+                (0.25, 60, 0),
+                (0.25, 62, 100),
+                (0.5, 62, 0),
+                (0.625, 64, 100),
+                (0.75, 64, 0),
+            ],
+        );
+        assert_eq!(error.onsets(), 3);
+        assert!((error.max() - 0.0202).abs() < 1e-9);
+        assert!((error.mean() - (0.02 + 0.0202 + 0.01) / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_mixed_grid() {
+        // Triplet eighths in the first beat, sixteenths in the second.
+        let beats = BeatGrid::steady(60.0, 0.0, 4).unwrap();
+        let seq = PitchSequence::from_notes(&[
+            note(0.0, 0.3, 60),
+            note(0.32, 0.6, 62),
+            note(0.68, 0.95, 64),
+            note(1.02, 1.2, 65),
+            note(1.26, 1.45, 67),
+            note(1.49, 1.98, 69),
+        ]);
+        let onsets = |seq: &PitchSequence| {
+            note_times(seq)
+                .iter()
+                .filter(|(_, _, v)| *v > 0)
+                .map(|(t, _, _)| *t)
+                .collect::<Vec<_>>()
+        };
+        let (straight, straight_error) = quantized(&seq, &beats, &Grid::SIXTEENTHS);
+        assert_eq!(onsets(&straight), [0.0, 0.25, 0.75, 1.0, 1.25, 1.5]);
+        let (mixed, mixed_error) = quantized(&seq, &beats, &Grid::Mixed(vec![4, 3]));
+        let expected = [0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0, 1.25, 1.5];
+        for (actual, expected) in onsets(&mixed).iter().zip(expected.iter()) {
+            assert!((actual - expected).abs() < 1e-9);
+        }
+        assert!(mixed_error.mean() < straight_error.mean());
+    }
+
+    #[test]
+    fn test_quantized_melody() {
+        // The 0.0001 second gap that random_melody_from puts between notes.
+        let beats = BeatGrid::steady(120.0, 0.0, 4).unwrap();
+        let melody = [
+            (0.5, 60, 127),
+            (0.0001, 60, 0),
+            (0.2499, 62, 127),
+            (0.0, 62, 0),
+            (0.02, 64, 127),
+            (0.0, 64, 0),
+        ];
+        let (result, error) = quantized_melody(&melody, 0.0, &beats, &Grid::SIXTEENTHS);
+        assert_close(
+            &result,
+            &[
+                (0.5, 60, 127),
+                (0.0, 60, 0),
+                (0.25, 62, 127),
+                (0.0, 62, 0),
+                (0.125, 64, 127),
+                (0.0, 64, 0),
+            ],
+        );
+        assert_eq!(error.onsets(), 3);
+        assert!(error.max() < 0.001);
+    }
+}
@@ -0,0 +1,166 @@
+use enum_iterator::all;
+
+use crate::{Key, ReducedPitches, ScaleMode};
+
+/// How well one root and `ScaleMode` covers a passage. `fit` is the share of
+/// the passage's weight that falls on scale tones; `outside` lists the pitch
+/// classes that sound but are not in the scale.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScaleMatch {
+    key: Key,
+    fit: f64,
+    outside: Vec<u8>,
+}
+
+impl ScaleMatch {
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    pub fn fit(&self) -> f64 {
+        self.fit
+    }
+
+    pub fn outside(&self) -> &[u8] {
+        &self.outside
+    }
+}
+
+/// Every root and `ScaleMode` whose scale contains all of `pitches`, with
+/// the smallest scales first.
+pub fn scales_containing(pitches: ReducedPitches) -> Vec<Key> {
+    ranked_scales_from_pitches(pitches)
+        .iter()
+        .filter(|m| m.outside.is_empty())
+        .map(|m| m.key)
+        .collect()
+}
+
+pub fn ranked_scales_from_pitches(pitches: ReducedPitches) -> Vec<ScaleMatch> {
+    let mut weights = [0.0; 12];
+    for pc in pitches.iter() {
+        weights[pc as usize] = 1.0;
+    }
+    ranked_scales(&weights)
+}
+
+/// Weights each pitch class by how long it sounds in `melody`, which is in
+/// the form returned by `durations_notes_from`.
+pub fn ranked_scales_from_melody(melody: &[(f64, u8, u8)]) -> Vec<ScaleMatch> {
+    let mut weights = [0.0; 12];
+    for (duration, note, velocity) in melody.iter() {
+        if *velocity > 0 {
+            weights[(note % 12) as usize] += duration;
+        }
+    }
+    ranked_scales(&weights)
+}
+
+/// Every root and `ScaleMode`, ranked by fit. Among equal fits, scales with
+/// fewer notes come first, since a larger scale containing the same tones
+/// says less about the passage; then those whose root carries more weight.
+/// Empty if no pitch class has any weight.
+pub fn ranked_scales(pitch_class_weights: &[f64; 12]) -> Vec<ScaleMatch> {
+    let total = pitch_class_weights.iter().sum::<f64>();
+    if total <= 0.0 {
+        return vec![];
+    }
+    let mut result = vec![];
+    for mode in all::<ScaleMode>() {
+        for root in 0..12 {
+            let key = Key::from_pitch_class(root, mode);
+            let scale = key.pitch_classes();
+            let outside = (0..12)
+                .filter(|pc| pitch_class_weights[*pc as usize] > 0.0 && !scale.contains(pc))
+                .collect::<Vec<_>>();
+            let outside_weight = outside
+                .iter()
+                .map(|pc| pitch_class_weights[*pc as usize])
+                .sum::<f64>();
+            result.push(ScaleMatch {
+                key,
+                fit: (total - outside_weight) / total,
+                outside,
+            });
+        }
+    }
+    result.sort_by(|m1, m2| {
+        m2.fit
+            .total_cmp(&m1.fit)
+            .then(m1.key.mode().num_notes().cmp(&m2.key.mode().num_notes()))
+            .then(
+                pitch_class_weights[m2.key.tonic().pitch_class() as usize]
+                    .total_cmp(&pitch_class_weights[m1.key.tonic().pitch_class() as usize]),
+            )
+    });
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use midi_msg::Channel;
+    use midi_note_recorder::midi_msg_from;
+
+    use crate::scale_finder::{
+        ranked_scales_from_melody, ranked_scales_from_pitches, scales_containing,
+    };
+    use crate::{ActivePitches, ReducedPitches, ScaleMode};
+
+    fn reduced_from(pitches: &[u8]) -> ReducedPitches {
+        let mut active = ActivePitches::default();
+        for pitch in pitches {
+            active.update_from(&midi_msg_from(Channel::Ch1, *pitch, 100));
+        }
+        ReducedPitches::new(active)
+    }
+
+    #[test]
+    fn test_scales_containing() {
+        let pentatonic = reduced_from(&[57, 60, 62, 64, 67]);
+        let keys = scales_containing(pentatonic)
+            .iter()
+            .map(|k| k.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(keys[..2], ["C  MajorPentatonic", "A  MinorPentatonic"]);
+        for expected in ["C  Major", "A  Minor", "G  Major", "A  Blues", "D  Dorian"] {
+            assert!(keys.contains(&expected.to_string()));
+        }
+        assert!(!keys.contains(&"E♭ Major".to_string()));
+
+        let ranked = ranked_scales_from_pitches(pentatonic);
+        assert_eq!(ranked.len(), 12 * enum_iterator::cardinality::<ScaleMode>());
+        assert!(ranked.windows(2).all(|w| w[0].fit() >= w[1].fit()));
+    }
+
+    #[test]
+    fn test_ranked_scales_from_melody() {
+        let melody = vec![
+            (1.0, 57, 80),
+            (0.0, 57, 0),
+            (0.5, 60, 80),
+            (0.0, 60, 0),
+            (0.5, 62, 80),
+            (0.0, 62, 0),
+            (0.5, 64, 80),
+            (0.0, 64, 0),
+            (0.5, 67, 80),
+            (0.0, 67, 0),
+            (0.25, 66, 80),
+            (0.0, 66, 0),
+            (2.0, 69, 80),
+            (0.0, 69, 0),
+        ];
+        let ranked = ranked_scales_from_melody(&melody);
+        let best = &ranked[0];
+        assert_eq!(best.key().to_string(), "A  Dorian");
+        assert_eq!(best.fit(), 1.0);
+        assert!(best.outside().is_empty());
+
+        let pentatonic = ranked
+            .iter()
+            .find(|m| m.key().to_string() == "A  MinorPentatonic")
+            .unwrap();
+        assert_eq!(pentatonic.outside(), &[6]);
+        assert!((pentatonic.fit() - 5.0 / 5.25).abs() < 1e-10);
+    }
+}
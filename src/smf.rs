@@ -0,0 +1,477 @@
+//! Reading Standard MIDI Files of type 0 or 1 into a `Recording`, and
+//! writing timed messages out as type 1 files.
+
+use anyhow::{anyhow, bail};
+use midi_msg::{Channel, ChannelVoiceMsg, MidiMsg};
+use midi_note_recorder::{midi_msg_from, Recording};
+
+/// Tempo in effect until the first tempo event: 120 beats per minute.
+const DEFAULT_MICROSECONDS_PER_QUARTER: u32 = 500_000;
+
+pub const DEFAULT_TICKS_PER_QUARTER: u16 = 480;
+
+/// Loads `filename` as a Standard MIDI File if it ends in ".mid" or ".midi",
+/// and as a JSON `Recording` otherwise. `keep_percussion` is passed on to
+/// `recording_from_smf`; JSON recordings are loaded whole.
+pub fn read_recording(filename: &str, keep_percussion: bool) -> anyhow::Result<Recording> {
+    let lower = filename.to_lowercase();
+    if lower.ends_with(".mid") || lower.ends_with(".midi") {
+        recording_from_smf(&std::fs::read(filename)?, keep_percussion)
+    } else {
+        Ok(Recording::from_file(filename)?)
+    }
+}
+
+/// Merges every track into one `Recording`, timed in seconds through the
+/// file's tempo map. Channel messages keep their channels. Unless
+/// `keep_percussion` is set, channel 10 is dropped: General MIDI uses it for
+/// percussion, whose note numbers name drums rather than pitches. Meta
+/// events other than tempo changes, and system exclusive messages, are
+/// skipped.
+pub fn recording_from_smf(bytes: &[u8], keep_percussion: bool) -> anyhow::Result<Recording> {
+    let mut reader = Reader { bytes, position: 0 };
+    let (format, num_tracks, division) = reader.header()?;
+    if format > 1 {
+        bail!("Standard MIDI File type {format} is not supported; only types 0 and 1");
+    }
+    let mut events = vec![];
+    let mut tracks_read = 0;
+    while tracks_read < num_tracks && !reader.at_end() {
+        let (id, length) = reader.chunk_header()?;
+        let chunk = reader.take(length)?;
+        if id == *b"MTrk" {
+            track_events(chunk, keep_percussion, &mut events)?;
+            tracks_read += 1;
+        }
+    }
+    events.sort_by_key(|(tick, _)| *tick);
+
+    let mut result = Recording::default();
+    let mut microseconds_per_quarter = DEFAULT_MICROSECONDS_PER_QUARTER;
+    let mut last_tick = 0;
+    let mut seconds = 0.0;
+    for (tick, event) in events {
+        seconds += (tick - last_tick) as f64 * division.seconds_per_tick(microseconds_per_quarter);
+        last_tick = tick;
+        match event {
+            Event::Tempo(tempo) => microseconds_per_quarter = tempo,
+            Event::Message(msg) => result.add_message(seconds, &msg),
+        }
+    }
+    Ok(result)
+}
+
+/// Writes a type 1 file with a tempo track followed by one track per entry
+/// of `tracks`, each a name and messages timed in seconds, such as a
+/// `Recording`'s `midi_queue()` or the output of the `generator` functions.
+pub fn write_smf(
+    filename: &str,
+    tracks: &[(&str, &[(f64, MidiMsg)])],
+    beats_per_minute: f64,
+    ticks_per_quarter: u16,
+) -> anyhow::Result<()> {
+    std::fs::write(
+        filename,
+        smf_from_tracks(tracks, beats_per_minute, ticks_per_quarter)?,
+    )?;
+    Ok(())
+}
+
+/// Times are rounded to the nearest tick at the constant tempo
+/// `beats_per_minute`. Only channel messages are written.
+pub fn smf_from_tracks(
+    tracks: &[(&str, &[(f64, MidiMsg)])],
+    beats_per_minute: f64,
+    ticks_per_quarter: u16,
+) -> anyhow::Result<Vec<u8>> {
+    if beats_per_minute.is_nan()
+        || beats_per_minute <= 0.0
+        || ticks_per_quarter == 0
+        || ticks_per_quarter >= 0x8000
+    {
+        bail!("Tempo must be positive and ticks per quarter between 1 and 32767");
+    }
+    let microseconds_per_quarter = (60_000_000.0 / beats_per_minute).round() as u32;
+    if microseconds_per_quarter == 0 || microseconds_per_quarter > 0xFF_FFFF {
+        bail!("Tempo of {beats_per_minute} beats per minute cannot be written");
+    }
+    let num_tracks = u16::try_from(tracks.len() + 1)?;
+    let mut header = vec![];
+    header.extend_from_slice(&1u16.to_be_bytes());
+    header.extend_from_slice(&num_tracks.to_be_bytes());
+    header.extend_from_slice(&ticks_per_quarter.to_be_bytes());
+    let mut result = chunk_from(b"MThd", &header);
+
+    let mut tempo_track = vec![0x00, 0xFF, 0x51, 0x03];
+    tempo_track.extend_from_slice(&microseconds_per_quarter.to_be_bytes()[1..]);
+    tempo_track.extend_from_slice(&END_OF_TRACK);
+    result.extend(chunk_from(b"MTrk", &tempo_track));
+
+    let ticks_per_second = beats_per_minute / 60.0 * ticks_per_quarter as f64;
+    for (name, messages) in tracks {
+        result.extend(chunk_from(
+            b"MTrk",
+            &track_bytes(name, messages, ticks_per_second),
+        ));
+    }
+    Ok(result)
+}
+
+const END_OF_TRACK: [u8; 4] = [0x00, 0xFF, 0x2F, 0x00];
+
+fn track_bytes(name: &str, messages: &[(f64, MidiMsg)], ticks_per_second: f64) -> Vec<u8> {
+    let mut result = vec![0x00, 0xFF, 0x03];
+    push_variable_length(&mut result, name.len() as u64);
+    result.extend_from_slice(name.as_bytes());
+
+    let mut timed = messages
+        .iter()
+        .filter(|(_, msg)| {
+            matches!(
+                msg,
+                MidiMsg::ChannelVoice { .. } | MidiMsg::ChannelMode { .. }
+            )
+        })
+        .map(|(time, msg)| ((time.max(0.0) * ticks_per_second).round() as u64, msg))
+        .collect::<Vec<_>>();
+    timed.sort_by_key(|(tick, _)| *tick);
+    let mut last_tick = 0;
+    for (tick, msg) in timed {
+        push_variable_length(&mut result, tick - last_tick);
+        last_tick = tick;
+        result.extend(msg.to_midi());
+    }
+    result.extend_from_slice(&END_OF_TRACK);
+    result
+}
+
+fn chunk_from(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut result = id.to_vec();
+    result.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    result.extend_from_slice(data);
+    result
+}
+
+fn push_variable_length(bytes: &mut Vec<u8>, value: u64) {
+    let mut groups = vec![(value & 0x7F) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        groups.push((rest & 0x7F) as u8 | 0x80);
+        rest >>= 7;
+    }
+    bytes.extend(groups.iter().rev());
+}
+
+enum Event {
+    Tempo(u32),
+    Message(MidiMsg),
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Division {
+    TicksPerQuarter(u16),
+    Smpte {
+        frames_per_second: f64,
+        ticks_per_frame: u8,
+    },
+}
+
+impl Division {
+    fn seconds_per_tick(&self, microseconds_per_quarter: u32) -> f64 {
+        match self {
+            Division::TicksPerQuarter(ticks) => {
+                microseconds_per_quarter as f64 / 1_000_000.0 / *ticks as f64
+            }
+            Division::Smpte {
+                frames_per_second,
+                ticks_per_frame,
+            } => 1.0 / (frames_per_second * *ticks_per_frame as f64),
+        }
+    }
+}
+
+/// Appends the tempo changes and channel messages of one track, keyed by
+/// absolute tick, leaving out channel 10 unless `keep_percussion` is set.
+fn track_events(
+    chunk: &[u8],
+    keep_percussion: bool,
+    events: &mut Vec<(u64, Event)>,
+) -> anyhow::Result<()> {
+    let mut reader = Reader {
+        bytes: chunk,
+        position: 0,
+    };
+    let mut tick = 0;
+    let mut running_status = None;
+    while !reader.at_end() {
+        tick += reader.variable_length()? as u64;
+        let mut status = reader.byte()?;
+        match status {
+            0xFF => {
+                running_status = None;
+                let kind = reader.byte()?;
+                let length = reader.variable_length()?;
+                let data = reader.take(length)?;
+                match (kind, data) {
+                    (0x2F, _) => break,
+                    (0x51, [a, b, c]) => {
+                        events.push((tick, Event::Tempo(u32::from_be_bytes([0, *a, *b, *c]))))
+                    }
+                    _ => {}
+                }
+            }
+            0xF0 | 0xF7 => {
+                running_status = None;
+                let length = reader.variable_length()?;
+                reader.take(length)?;
+            }
+            _ => {
+                if status < 0x80 {
+                    status = running_status
+                        .ok_or_else(|| anyhow!("Data byte without a status at tick {tick}"))?;
+                    reader.position -= 1;
+                }
+                running_status = Some(status);
+                let num_data = if matches!(status & 0xF0, 0xC0 | 0xD0) {
+                    1
+                } else {
+                    2
+                };
+                let mut message = vec![status];
+                message.extend_from_slice(reader.take(num_data)?);
+                if let Ok((msg, _)) = MidiMsg::from_midi(&message) {
+                    if keep_percussion || !is_percussion(&msg) {
+                        events.push((tick, Event::Message(normalized(msg))));
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_percussion(msg: &MidiMsg) -> bool {
+    matches!(
+        msg,
+        MidiMsg::ChannelVoice {
+            channel: Channel::Ch10,
+            ..
+        }
+    )
+}
+
+/// Notes are rewritten the way `midi_note_recorder` stores them, with note
+/// offs as note ons of velocity 0.
+fn normalized(msg: MidiMsg) -> MidiMsg {
+    match msg {
+        MidiMsg::ChannelVoice {
+            channel,
+            msg: ChannelVoiceMsg::NoteOn { note, velocity },
+        } => midi_msg_from(channel, note, velocity),
+        MidiMsg::ChannelVoice {
+            channel,
+            msg: ChannelVoiceMsg::NoteOff { note, .. },
+        } => midi_msg_from(channel, note, 0),
+        msg => msg,
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn at_end(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    fn take(&mut self, length: usize) -> anyhow::Result<&'a [u8]> {
+        let end = self.position + length;
+        if end > self.bytes.len() {
+            bail!("Standard MIDI File ends in the middle of a chunk or event");
+        }
+        let result = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(result)
+    }
+
+    fn byte(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn variable_length(&mut self) -> anyhow::Result<usize> {
+        let mut result = 0;
+        for _ in 0..4 {
+            let byte = self.byte()?;
+            result = (result << 7) | (byte & 0x7F) as usize;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+        bail!("Variable-length quantity longer than four bytes")
+    }
+
+    fn chunk_header(&mut self) -> anyhow::Result<([u8; 4], usize)> {
+        let id = self.take(4)?;
+        let length = self.u32()? as usize;
+        Ok(([id[0], id[1], id[2], id[3]], length))
+    }
+
+    fn header(&mut self) -> anyhow::Result<(u16, u16, Division)> {
+        let (id, length) = self.chunk_header()?;
+        if id != *b"MThd" || length < 6 {
+            bail!("Not a Standard MIDI File: missing MThd header");
+        }
+        let header = self.take(length)?;
+        let mut reader = Reader {
+            bytes: header,
+            position: 0,
+        };
+        let format = reader.u16()?;
+        let num_tracks = reader.u16()?;
+        let division = reader.u16()?;
+        let division = if division & 0x8000 == 0 {
+            if division == 0 {
+                bail!("Standard MIDI File has zero ticks per quarter");
+            }
+            Division::TicksPerQuarter(division)
+        } else {
+            let frames = -((division >> 8) as u8 as i8 as i16);
+            let ticks_per_frame = (division & 0xFF) as u8;
+            if ticks_per_frame == 0 {
+                bail!("Standard MIDI File has zero ticks per SMPTE frame");
+            }
+            Division::Smpte {
+                frames_per_second: if frames == 29 { 29.97 } else { frames as f64 },
+                ticks_per_frame,
+            }
+        };
+        Ok((format, num_tracks, division))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use midi_msg::Channel;
+    use midi_note_recorder::{midi_msg_from, note_velocity_from};
+
+    use crate::smf::{chunk_from as chunk, recording_from_smf, smf_from_tracks};
+
+    #[test]
+    fn test_type_1_with_tempo_map() {
+        // 96 ticks per quarter: one second per quarter, then half a second
+        // from tick 96 onward.
+        let tempo_track = [
+            0x00, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40, // 1,000,000 us at tick 0
+            0x60, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // 500,000 us at tick 96
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let note_track = [
+            0x00, 0x90, 60, 100, // note on, channel 1
+            0x00, 64, 90, // running status
+            0x60, 0x80, 60, 0, // note off at tick 96
+            0x30, 0x90, 64, 0, // velocity 0 at tick 144
+            0x00, 0x99, 36, 100, // channel 10 percussion
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let mut bytes = chunk(b"MThd", &[0, 1, 0, 2, 0, 96]);
+        bytes.extend(chunk(b"MTrk", &tempo_track));
+        bytes.extend(chunk(b"MTrk", &note_track));
+
+        let recording = recording_from_smf(&bytes, false).unwrap();
+        let events = recording
+            .midi_queue()
+            .iter()
+            .map(|(t, msg)| (*t, note_velocity_from(msg).unwrap()))
+            .collect::<Vec<_>>();
+        let expected = [
+            (0.0, (60, 100)),
+            (0.0, (64, 90)),
+            (1.0, (60, 0)),
+            (1.25, (64, 0)),
+        ];
+        assert_eq!(events.len(), expected.len());
+        for ((t, note), (expected_t, expected_note)) in events.iter().zip(expected.iter()) {
+            assert!((t - expected_t).abs() < 1e-9);
+            assert_eq!(note, expected_note);
+        }
+
+        let with_percussion = recording_from_smf(&bytes, true).unwrap().midi_queue();
+        assert_eq!(with_percussion.len(), expected.len() + 1);
+        assert!(with_percussion.iter().any(
+            |(t, msg)| (t - 1.25).abs() < 1e-9 && *msg == midi_msg_from(Channel::Ch10, 36, 100)
+        ));
+    }
+
+    #[test]
+    fn test_bad_files() {
+        assert!(recording_from_smf(b"RIFF", false).is_err());
+        let mut bytes = chunk(b"MThd", &[0, 2, 0, 1, 0, 96]);
+        bytes.extend(chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]));
+        assert!(recording_from_smf(&bytes, false).is_err());
+        let mut bytes = chunk(b"MThd", &[0, 0, 0, 1, 0, 96]);
+        bytes.extend(chunk(b"MTrk", &[0x00, 60, 100]));
+        assert!(recording_from_smf(&bytes, false).is_err());
+        // Zero ticks per quarter, and 25 frames per second of zero ticks.
+        for division in [[0, 0], [0xE7, 0]] {
+            let mut bytes = chunk(b"MThd", &[0, 0, 0, 1, division[0], division[1]]);
+            bytes.extend(chunk(b"MTrk", &[0x00, 0xFF, 0x2F, 0x00]));
+            assert!(recording_from_smf(&bytes, false).is_err());
+        }
+    }
+
+    #[test]
+    fn test_write_tracks() {
+        let source = [
+            (0.0, midi_msg_from(Channel::Ch1, 48, 90)),
+            (2.0, midi_msg_from(Channel::Ch1, 48, 0)),
+        ];
+        let melody = [
+            (0.5, midi_msg_from(Channel::Ch2, 72, 127)),
+            (0.25, midi_msg_from(Channel::Ch2, 67, 127)),
+            (0.75, midi_msg_from(Channel::Ch2, 72, 0)),
+            (70.0, midi_msg_from(Channel::Ch2, 67, 0)),
+        ];
+        let bytes = smf_from_tracks(
+            &[("Source", &source[..]), ("Melody", &melody[..])],
+            120.0,
+            96,
+        )
+        .unwrap();
+        assert_eq!(&bytes[8..14], &[0, 1, 0, 3, 0, 96]);
+
+        let recording = recording_from_smf(&bytes, false).unwrap();
+        let events = recording
+            .midi_queue()
+            .iter()
+            .map(|(t, msg)| (*t, note_velocity_from(msg).unwrap()))
+            .collect::<Vec<_>>();
+        let expected = [
+            (0.0, (48, 90)),
+            (0.25, (67, 127)),
+            (0.5, (72, 127)),
+            (0.75, (72, 0)),
+            (2.0, (48, 0)),
+            (70.0, (67, 0)),
+        ];
+        assert_eq!(events.len(), expected.len());
+        for ((t, note), (expected_t, expected_note)) in events.iter().zip(expected.iter()) {
+            assert!((t - expected_t).abs() < 1e-9);
+            assert_eq!(note, expected_note);
+        }
+
+        assert!(smf_from_tracks(&[("Source", &source[..])], 0.0, 96).is_err());
+        assert!(smf_from_tracks(&[("Source", &source[..])], 120.0, 0).is_err());
+    }
+}
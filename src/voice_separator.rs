@@ -0,0 +1,256 @@
+use midi_note_recorder::Recording;
+
+use crate::{Note, PitchSequence};
+
+/// Notes starting within this many seconds of the first note of a chord
+/// count as struck with it, so rolled or uneven chords stay together.
+const CHORD_SPREAD: f64 = 0.05;
+
+/// How long, in seconds, a stream's last note may still sound when the next
+/// begins, allowing for legato playing.
+const LEGATO_OVERLAP: f64 = 0.1;
+
+/// The cost, in semitones, of starting a new stream. A note joins an
+/// existing stream only if its leap plus the gap penalty costs less.
+const NEW_STREAM_COST: f64 = 7.0;
+
+/// Cost, in semitones per second, of the silence between a stream's last
+/// note and the next, so that after a rest a note joins the stream that
+/// sounded most recently.
+const GAP_COST: f64 = 2.0;
+
+pub fn melody_and_accompaniment_from(recording: &Recording) -> (Recording, Recording) {
+    let (melody, accompaniment) = PitchSequence::new(recording).melody_and_accompaniment();
+    (melody.recording(), accompaniment.recording())
+}
+
+/// The notes of `seq` split into monophonic streams by pitch proximity and
+/// continuity, highest first by mean pitch. Notes are taken a chord at a
+/// time, highest first, and matched against the streams free to continue
+/// without letting streams cross; each match costs the leap in semitones
+/// plus `GAP_COST` for every second of silence before it, and the matching
+/// with the least total cost wins. Notes left unmatched start new streams.
+pub fn streams(seq: &PitchSequence) -> Vec<Vec<Note>> {
+    let notes = seq.notes();
+    let mut streams: Vec<Vec<Note>> = vec![];
+    let mut i = 0;
+    while i < notes.len() {
+        let chord_start = notes[i].start();
+        let chord_end = notes[i..]
+            .iter()
+            .position(|n| n.start() >= chord_start + CHORD_SPREAD)
+            .map_or(notes.len(), |j| i + j);
+        let mut chord = notes[i..chord_end].to_vec();
+        chord.sort_by(|a, b| b.pitch().cmp(&a.pitch()));
+        i = chord_end;
+
+        let mut free = (0..streams.len())
+            .filter(|s| last_note(&streams[*s]).end() <= chord_start + LEGATO_OVERLAP)
+            .collect::<Vec<_>>();
+        free.sort_by(|a, b| {
+            last_note(&streams[*b])
+                .pitch()
+                .cmp(&last_note(&streams[*a]).pitch())
+        });
+        let free_last = free
+            .iter()
+            .map(|s| *last_note(&streams[*s]))
+            .collect::<Vec<_>>();
+        let mut new_streams = vec![];
+        for (note, stream) in matched(&chord, &free_last) {
+            match stream {
+                Some(s) => streams[free[s]].push(chord[note]),
+                None => new_streams.push(vec![chord[note]]),
+            }
+        }
+        streams.extend(new_streams);
+    }
+    let mean_pitch = |stream: &Vec<Note>| {
+        stream.iter().map(|n| n.pitch() as f64).sum::<f64>() / stream.len() as f64
+    };
+    streams.sort_by(|a, b| mean_pitch(b).total_cmp(&mean_pitch(a)));
+    streams
+}
+
+/// `seq` split into the streams that mostly lie on top of the texture and
+/// those that don't: a note lies on top if no note in another stream
+/// sounds above it as it starts. Combining streams this way keeps a melody
+/// whole when a wide leap or a long rest breaks it into several streams.
+/// Only notes are kept.
+pub fn melody_and_accompaniment(seq: &PitchSequence) -> (PitchSequence, PitchSequence) {
+    let streams = streams(seq);
+    let mut melody = vec![];
+    let mut accompaniment = vec![];
+    for (i, stream) in streams.iter().enumerate() {
+        let on_top = stream
+            .iter()
+            .filter(|note| {
+                !streams
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .flat_map(|(_, other)| other.iter())
+                    .any(|other| {
+                        other.pitch() > note.pitch()
+                            && other.start() < note.start() + CHORD_SPREAD
+                            && other.end() > note.start()
+                    })
+            })
+            .count();
+        if on_top * 2 > stream.len() {
+            melody.extend(stream.iter().copied());
+        } else {
+            accompaniment.extend(stream.iter().copied());
+        }
+    }
+    (
+        PitchSequence::from_notes(&melody),
+        PitchSequence::from_notes(&accompaniment),
+    )
+}
+
+fn last_note(stream: &[Note]) -> &Note {
+    stream.last().unwrap()
+}
+
+#[derive(Copy, Clone)]
+enum Step {
+    Match,
+    SkipStream,
+    NewStream,
+}
+
+/// For each note of `chord`, the index into `free_last` of the stream it
+/// continues, or `None` for a new stream. Both are ordered highest first,
+/// and the matching keeps that order.
+fn matched(chord: &[Note], free_last: &[Note]) -> Vec<(usize, Option<usize>)> {
+    let cost = |note: &Note, last: &Note| {
+        (note.pitch() as f64 - last.pitch() as f64).abs()
+            + GAP_COST * (note.start() - last.end()).max(0.0)
+    };
+    let (rows, columns) = (chord.len() + 1, free_last.len() + 1);
+    let mut costs = vec![vec![0.0; columns]; rows];
+    let mut steps = vec![vec![Step::Match; columns]; rows];
+    for n in 0..rows {
+        for s in 0..columns {
+            let mut best: Option<(f64, Step)> = None;
+            if n > 0 && s > 0 {
+                let step_cost = cost(&chord[n - 1], &free_last[s - 1]);
+                if step_cost < NEW_STREAM_COST {
+                    best = Some((costs[n - 1][s - 1] + step_cost, Step::Match));
+                }
+            }
+            if s > 0 && best.is_none_or(|(c, _)| costs[n][s - 1] < c) {
+                best = Some((costs[n][s - 1], Step::SkipStream));
+            }
+            if n > 0 && best.is_none_or(|(c, _)| costs[n - 1][s] + NEW_STREAM_COST < c) {
+                best = Some((costs[n - 1][s] + NEW_STREAM_COST, Step::NewStream));
+            }
+            if let Some((c, step)) = best {
+                costs[n][s] = c;
+                steps[n][s] = step;
+            }
+        }
+    }
+
+    let mut result = vec![];
+    let (mut n, mut s) = (chord.len(), free_last.len());
+    while n > 0 || s > 0 {
+        match steps[n][s] {
+            Step::Match => {
+                result.push((n - 1, Some(s - 1)));
+                n -= 1;
+                s -= 1;
+            }
+            Step::SkipStream => s -= 1,
+            Step::NewStream => {
+                result.push((n - 1, None));
+                n -= 1;
+            }
+        }
+    }
+    result.reverse();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use midi_msg::Channel;
+    use midi_note_recorder::Recording;
+
+    use crate::voice_separator::{melody_and_accompaniment, streams};
+    use crate::{durations_notes_from, Note, PitchSequence};
+
+    #[test]
+    fn test_block_chords() {
+        let seq = PitchSequence::new(&Recording::from_file("up_down").unwrap());
+        let streams = streams(&seq);
+        assert_eq!(
+            streams.iter().map(|s| s.len()).collect::<Vec<_>>(),
+            [14, 14, 14]
+        );
+        let (melody, accompaniment) = melody_and_accompaniment(&seq);
+        let top = melody.notes().iter().map(|n| n.pitch()).collect::<Vec<_>>();
+        assert_eq!(
+            top,
+            [67, 69, 71, 72, 71, 69, 67, 65, 64, 62, 60, 59, 57, 55]
+        );
+        assert_eq!(accompaniment.notes().len(), 28);
+    }
+
+    #[test]
+    fn test_melody_over_chords() {
+        // Slightly rolled C and F chords on every beat at 120 beats per
+        // minute, under a melody with a rest, an octave leap and a dip below
+        // the top of the chords.
+        let mut notes = vec![];
+        for bar in 0..4 {
+            let chord = if bar % 2 == 0 {
+                [48, 52, 55]
+            } else {
+                [53, 57, 60]
+            };
+            for beat in 0..4 {
+                let start = bar as f64 * 2.0 + beat as f64 * 0.5;
+                for pitch in chord {
+                    let roll = 0.01 * (pitch % 3) as f64;
+                    notes.push(Note::new(start + roll, 0.45, pitch, 70, Channel::Ch1));
+                }
+            }
+        }
+        let line = [
+            (0.0, 0.25, 72),
+            (0.25, 0.25, 74),
+            (0.5, 0.5, 76),
+            (1.0, 0.5, 79),
+            (1.5, 0.45, 77),
+            (3.0, 0.5, 76),
+            (3.5, 0.5, 84),
+            (4.0, 1.0, 72),
+            (5.0, 0.5, 74),
+            (5.5, 0.5, 67),
+            (6.0, 0.9, 65),
+        ];
+        for (start, duration, pitch) in line {
+            notes.push(Note::new(start, duration, pitch, 100, Channel::Ch1));
+        }
+        let seq = PitchSequence::from_notes(&notes);
+        let (melody, accompaniment) = melody_and_accompaniment(&seq);
+
+        let melodic = durations_notes_from(&melody.recording())
+            .iter()
+            .filter(|(_, _, v)| *v > 0)
+            .map(|(_, n, _)| *n)
+            .collect::<Vec<_>>();
+        assert_eq!(melodic, line.iter().map(|(_, _, p)| *p).collect::<Vec<_>>());
+        // The accompaniment's chords come from the left hand alone.
+        let chords = accompaniment
+            .chords_starts_durations()
+            .iter()
+            .map(|(c, _, _)| c.notes().iter().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert!(chords.contains(&vec![48, 52, 55]));
+        assert!(chords.contains(&vec![53, 57, 60]));
+        assert!(chords.iter().flatten().all(|p| *p <= 60));
+    }
+}